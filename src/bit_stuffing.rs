@@ -0,0 +1,355 @@
+use std::io::Result as Res;
+use std::io::{Error, ErrorKind, Read, Write};
+
+use crate::endian::BitEndianness;
+use crate::read::{BitRead, BitReader};
+use crate::write::{BitWrite, BitWriter};
+
+/**
+	Specifies when a bit is stuffed into the stream.
+
+	Serial link protocols limit the length of runs of identical bits by inserting a stuffed bit after a run reaches a certain length. Stuffed bits start a new run.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StuffingRule {
+	/// After `run_length` consecutive bits not equal to `bit`, `bit` is stuffed.
+	Fixed { run_length: u8, bit: bool },
+	/// After `run_length` consecutive identical bits, a bit of the complementary value is stuffed.
+	Complement { run_length: u8 },
+}
+
+impl StuffingRule {
+	/// HDLC/PPP: a 0-bit is stuffed after five consecutive 1-bits.
+	pub const HDLC: Self = StuffingRule::Fixed { run_length: 5, bit: false };
+	/// USB: a 0-bit is stuffed after six consecutive 1-bits.
+	pub const USB: Self = StuffingRule::Fixed { run_length: 6, bit: false };
+	/// CAN: a complementary bit is stuffed after five consecutive identical bits.
+	pub const CAN: Self = StuffingRule::Complement { run_length: 5 };
+}
+
+/// Tracks the current run of identical bits.
+#[derive(Debug)]
+struct Run {
+	rule: StuffingRule,
+	/// Value of the bits in the current run.
+	bit: bool,
+	/// Length of the current run.
+	length: u8,
+}
+
+impl Run {
+	fn new(rule: StuffingRule) -> Self {
+		Self { rule, bit: false, length: 0 }
+	}
+
+	fn reset(&mut self) {
+		self.length = 0;
+	}
+
+	/// Adds a bit to the run, returning the bit that needs to be stuffed after it, if any.
+	fn push(&mut self, bit: bool) -> Option<bool> {
+		if self.length > 0 && bit == self.bit {
+			self.length = self.length.saturating_add(1);
+		} else {
+			self.bit = bit;
+			self.length = 1;
+		}
+		let stuffed = match self.rule {
+			StuffingRule::Fixed { run_length, bit: stuffed } if bit != stuffed && self.length >= run_length => Some(stuffed),
+			StuffingRule::Complement { run_length } if self.length >= run_length => Some(!bit),
+			_ => None,
+		};
+		if let Some(stuffed) = stuffed {
+			self.bit = stuffed;
+			self.length = 1;
+		}
+		stuffed
+	}
+}
+
+/**
+	Removes stuffed bits from the bits read from a `BitReader`.
+
+	If a bit that should have been stuffed has the wrong value, the read fails with an error of kind `InvalidData`.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitReader, BitDestuffer, BitRead, StuffingRule};
+
+	let reader = BEBitReader::new(&b"\xfb\xe0"[..]);
+	let mut reader = BitDestuffer::new(reader, StuffingRule::HDLC);
+	assert_eq!(reader.read_bits(8).unwrap(), 0xff);
+	assert_eq!(reader.read_bits(2).unwrap(), 0x03);
+	```
+*/
+#[derive(Debug)]
+pub struct BitDestuffer<E: BitEndianness, R: Read> {
+	inner: BitReader<E, R>,
+	run: Run,
+	/// Value of a stuffed bit that still needs to be read.
+	pending: Option<bool>,
+}
+
+impl<E: BitEndianness, R: Read> BitDestuffer<E, R> {
+	/// Creates a new `BitDestuffer` reading from `inner`, removing bits stuffed according to `rule`.
+	pub fn new(inner: BitReader<E, R>, rule: StuffingRule) -> Self {
+		Self {
+			inner,
+			run: Run::new(rule),
+			pending: None,
+		}
+	}
+
+	/**
+		Resets the run of identical bits, as if reading was started anew.
+
+		Any pending stuffed bit is discarded. Use this when the stuffed region of a protocol ends, or a new one begins.
+	*/
+	pub fn reset(&mut self) {
+		self.run.reset();
+		self.pending = None;
+	}
+
+	/// Gets a reference to the underlying `BitReader`.
+	pub fn get_ref(&self) -> &BitReader<E, R> {
+		&self.inner
+	}
+
+	/**
+		Gets a mutable reference to the underlying `BitReader`.
+
+		Bits read directly from the underlying reader are not destuffed and don't count towards the run of identical bits.
+	*/
+	pub fn get_mut(&mut self) -> &mut BitReader<E, R> {
+		&mut self.inner
+	}
+
+	/// Unwraps this `BitDestuffer`, returning the underlying `BitReader`.
+	pub fn into_inner(self) -> BitReader<E, R> {
+		self.inner
+	}
+
+	/// Reads a pending stuffed bit left by a failed read, returning an error if it has the wrong value.
+	pub(crate) fn read_pending(&mut self) -> Res<()> {
+		if let Some(expected) = self.pending {
+			let bit = self.inner.read_bit()?;
+			self.pending = None;
+			if bit != expected {
+				return Err(Error::new(ErrorKind::InvalidData, "stuff error: stuffed bit has the wrong value"));
+			}
+		}
+		Ok(())
+	}
+
+	/**
+		Reads a single bit, returning true for 1, false for 0.
+
+		A stuffed bit following the data bit is read as well. If reading the stuffed bit fails with an I/O error, it will be read again on the next read.
+	*/
+	pub fn read_bit(&mut self) -> Res<bool> {
+		self.read_pending()?;
+		let bit = self.inner.read_bit()?;
+		self.pending = self.run.push(bit);
+		match self.read_pending() {
+			Err(ref e) if e.kind() != ErrorKind::InvalidData => {}
+			res => res?,
+		}
+		Ok(bit)
+	}
+}
+
+impl<E: BitEndianness, R: Read> BitRead for BitDestuffer<E, R> {
+	fn read_bit(&mut self) -> Res<bool> {
+		BitDestuffer::read_bit(self)
+	}
+
+	fn msb_first(&self) -> bool {
+		E::msb_first()
+	}
+}
+
+/**
+	Inserts stuffed bits into the bits written to a `BitWriter`.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitWriter, BitStuffer, BitWrite, StuffingRule};
+
+	let writer = BEBitWriter::new(vec![]);
+	let mut writer = BitStuffer::new(writer, StuffingRule::HDLC);
+	writer.write_bits(0xff, 8).unwrap();
+	writer.write_bits(0x03, 2).unwrap();
+	let vec = writer.into_inner().into_inner().unwrap();
+	assert_eq!(vec, b"\xfb\xe0");
+	```
+*/
+#[derive(Debug)]
+pub struct BitStuffer<E: BitEndianness, W: Write> {
+	inner: BitWriter<E, W>,
+	run: Run,
+	/// Value of a stuffed bit that still needs to be written.
+	pending: Option<bool>,
+}
+
+impl<E: BitEndianness, W: Write> BitStuffer<E, W> {
+	/// Creates a new `BitStuffer` writing to `inner`, stuffing bits according to `rule`.
+	pub fn new(inner: BitWriter<E, W>, rule: StuffingRule) -> Self {
+		Self {
+			inner,
+			run: Run::new(rule),
+			pending: None,
+		}
+	}
+
+	/**
+		Resets the run of identical bits, as if writing was started anew.
+
+		Any pending stuffed bit is written first.
+	*/
+	pub fn reset(&mut self) -> Res<()> {
+		self.write_pending()?;
+		self.run.reset();
+		Ok(())
+	}
+
+	/// Gets a reference to the underlying `BitWriter`.
+	pub fn get_ref(&self) -> &BitWriter<E, W> {
+		&self.inner
+	}
+
+	/**
+		Gets a mutable reference to the underlying `BitWriter`.
+
+		Bits written directly to the underlying writer are not stuffed and don't count towards the run of identical bits.
+	*/
+	pub fn get_mut(&mut self) -> &mut BitWriter<E, W> {
+		&mut self.inner
+	}
+
+	/**
+		Unwraps this `BitStuffer`, returning the underlying `BitWriter`.
+
+		A stuffed bit is only left pending if writing it to the underlying writer failed. Call `reset` first to make sure it has been written.
+	*/
+	pub fn into_inner(self) -> BitWriter<E, W> {
+		self.inner
+	}
+
+	fn write_pending(&mut self) -> Res<()> {
+		if let Some(bit) = self.pending {
+			self.inner.write_bit(bit)?;
+			self.pending = None;
+		}
+		Ok(())
+	}
+
+	/**
+		Writes a single bit, writing 1 for true, 0 for false.
+
+		If a bit needs to be stuffed after the data bit, it is written as well. If writing the stuffed bit fails with `WouldBlock`, the data bit is reported as written, and the stuffed bit will be written again on the next write. Other errors are returned, even though the data bit has been written, and the stuffed bit is left pending as well.
+	*/
+	pub fn write_bit(&mut self, bit: bool) -> Res<()> {
+		self.write_pending()?;
+		self.inner.write_bit(bit)?;
+		self.pending = self.run.push(bit);
+		match self.write_pending() {
+			Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+			res => res?,
+		}
+		Ok(())
+	}
+}
+
+impl<E: BitEndianness, W: Write> BitWrite for BitStuffer<E, W> {
+	fn write_bit(&mut self, bit: bool) -> Res<()> {
+		BitStuffer::write_bit(self, bit)
+	}
+
+	fn msb_first(&self) -> bool {
+		E::msb_first()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::ErrorKind;
+	use crate::{BEBitReader, BEBitWriter, LEBitReader, LEBitWriter, BitDestuffer, BitRead, BitStuffer, BitWrite, StuffingRule};
+	use crate::test_util::{retry, ErrorWriter};
+
+	#[test]
+	fn stuff_fixed() {
+		let mut writer = BitStuffer::new(BEBitWriter::new(vec![]), StuffingRule::HDLC);
+		writer.write_bits(0x3e, 8).unwrap();
+		writer.write_bits(0x1f, 5).unwrap();
+		assert_eq!(writer.into_inner().into_inner().unwrap(), b"\x3e\x7c");
+	}
+
+	#[test]
+	fn stuff_complement() {
+		let mut writer = BitStuffer::new(BEBitWriter::new(vec![]), StuffingRule::CAN);
+		writer.write_bits(0x00, 8).unwrap();
+		writer.write_bits(0x0f, 4).unwrap();
+		assert_eq!(writer.into_inner().into_inner().unwrap(), b"\x04\x78");
+	}
+
+	#[test]
+	fn stuff_error() {
+		let mut writer = BitStuffer::new(BEBitWriter::new(ErrorWriter::new(ErrorKind::WouldBlock)), StuffingRule::HDLC);
+		for i in 0..16 {
+			retry(|| writer.write_bit(0xfd7f_u16 & 0x8000 >> i != 0));
+		}
+		let mut writer = writer.into_inner();
+		retry(|| writer.align());
+		assert_eq!(writer.into_inner().unwrap().data, b"\xfa\xbe\xc0");
+		let mut writer = BitStuffer::new(BEBitWriter::new(ErrorWriter::new(ErrorKind::Other)), StuffingRule::HDLC);
+		writer.write_bits(0x0f, 6).unwrap();
+		assert_eq!(writer.write_bit(true).unwrap_err().kind(), ErrorKind::Other);
+		writer.write_bit(true).unwrap();
+		assert_eq!((&writer.get_ref().get_ref().data[..], writer.get_ref().bit_offset()), (&b"\x3e"[..], 1));
+	}
+
+	#[test]
+	fn stuff_le() {
+		let mut writer = BitStuffer::new(LEBitWriter::new(vec![]), StuffingRule::HDLC);
+		writer.write_bits(0xff, 8).unwrap();
+		assert_eq!(writer.into_inner().into_inner().unwrap(), b"\xdf\x01");
+	}
+
+	#[test]
+	fn destuff_fixed() {
+		let mut reader = BitDestuffer::new(BEBitReader::new(&b"\x3e\x7c"[..]), StuffingRule::HDLC);
+		assert_eq!(reader.read_bits(8).unwrap(), 0x3e);
+		assert_eq!(reader.read_bits(5).unwrap(), 0x1f);
+		assert_eq!(reader.get_ref().buffered_bits(), 1);
+	}
+
+	#[test]
+	fn destuff_complement() {
+		let mut reader = BitDestuffer::new(BEBitReader::new(&b"\x04\x78"[..]), StuffingRule::CAN);
+		assert_eq!(reader.read_bits(8).unwrap(), 0x00);
+		assert_eq!(reader.read_bits(4).unwrap(), 0x0f);
+	}
+
+	#[test]
+	fn destuff_le() {
+		let mut reader = BitDestuffer::new(LEBitReader::new(&b"\xdf\x01"[..]), StuffingRule::HDLC);
+		assert_eq!(reader.read_bits(8).unwrap(), 0xff);
+	}
+
+	#[test]
+	fn destuff_error() {
+		let mut reader = BitDestuffer::new(BEBitReader::new(&b"\xfc"[..]), StuffingRule::HDLC);
+		assert_eq!(reader.read_bits(4).unwrap(), 0x0f);
+		assert_eq!(reader.read_bit().unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn destuff_reset() {
+		let mut reader = BitDestuffer::new(BEBitReader::new(&b"\xf8\xf8"[..]), StuffingRule::HDLC);
+		assert_eq!(reader.read_bits(4).unwrap(), 0x0f);
+		reader.reset();
+		assert_eq!(reader.read_bits(8).unwrap(), 0x8f);
+	}
+}
@@ -0,0 +1,317 @@
+use std::io::Result as Res;
+use std::io::{Error, ErrorKind, Read, Write};
+
+/**
+	Removes JPEG-style byte stuffing from something implementing [`Read`], stopping at markers.
+
+	In JPEG entropy-coded segments, every `0xff` data byte is followed by a stuffed `0x00` byte, so that it can't be mistaken for a marker. This reader removes the stuffed bytes transparently. When a marker (`0xff` followed by anything other than `0x00`) is encountered, the reader stops and reports end of file. The marker can then be retrieved using `marker`. Fill bytes (additional `0xff` bytes before a marker) are skipped.
+
+	To read bits from the unstuffed data, use this reader as the data source of a `BitReader`. Once the `BitReader` reaches the marker, `BitReader::buffered_bits` returns the number of bits left over in the last byte before the marker.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitReader, ByteStuffedReader};
+
+	let data = b"\xff\x00\xa0\xff\xd9";
+	let mut reader = BEBitReader::new(ByteStuffedReader::new(&data[..]));
+	assert_eq!(reader.read_bits(8).unwrap(), 0xff);
+	assert_eq!(reader.read_bits(3).unwrap(), 5);
+	assert!(reader.read_bits(8).is_err());
+	assert_eq!(reader.get_ref().marker(), Some(0xd9));
+	assert_eq!(reader.buffered_bits(), 5);
+	```
+
+	[`Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
+*/
+#[derive(Debug)]
+pub struct ByteStuffedReader<R: Read> {
+	/// Data to read from.
+	inner: R,
+	/// Whether a `0xff` byte has been read from the inner reader, but the byte following it hasn't.
+	pending_ff: bool,
+	/// The marker the reader stopped at, if any.
+	marker: Option<u8>,
+}
+
+impl<R: Read> ByteStuffedReader<R> {
+	/// Creates a new `ByteStuffedReader` reading stuffed data from `inner`.
+	pub fn new(inner: R) -> Self {
+		Self {
+			inner,
+			pending_ff: false,
+			marker: None,
+		}
+	}
+
+	/// Returns the marker the reader stopped at, or `None` if no marker has been encountered yet.
+	pub fn marker(&self) -> Option<u8> {
+		self.marker
+	}
+
+	/**
+		Continues reading after a marker, returning the marker that was encountered.
+
+		This is useful for markers that occur within entropy-coded data, like the `RSTn` restart markers.
+	*/
+	pub fn resume(&mut self) -> Option<u8> {
+		self.marker.take()
+	}
+
+	/// Gets a reference to the underlying reader.
+	pub fn get_ref(&self) -> &R {
+		&self.inner
+	}
+
+	/**
+		Gets a mutable reference to the underlying reader.
+
+		Reading from the underlying reader while a `0xff` byte is pending will corrupt this reader.
+	*/
+	pub fn get_mut(&mut self) -> &mut R {
+		&mut self.inner
+	}
+
+	/**
+		Unwraps this `ByteStuffedReader`, returning the underlying reader.
+
+		If the reader stopped at a marker, the underlying reader is positioned directly after the marker.
+	*/
+	pub fn into_inner(self) -> R {
+		self.inner
+	}
+
+	/// Reads a single byte from the inner reader, returning `None` at end of file.
+	fn read_byte(&mut self) -> Res<Option<u8>> {
+		let mut temp = [0; 1];
+		loop {
+			match self.inner.read(&mut temp) {
+				Ok(0) => return Ok(None),
+				Ok(_) => return Ok(Some(temp[0])),
+				Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Reads a single unstuffed byte, returning `None` at end of file or at a marker.
+	fn read_unstuffed(&mut self) -> Res<Option<u8>> {
+		if self.marker.is_some() {
+			return Ok(None);
+		}
+		if !self.pending_ff {
+			match self.read_byte()? {
+				Some(0xff) => self.pending_ff = true,
+				byte => return Ok(byte),
+			}
+		}
+		loop {
+			match self.read_byte()? {
+				Some(0xff) => {}
+				Some(0x00) => {
+					self.pending_ff = false;
+					return Ok(Some(0xff));
+				}
+				Some(marker) => {
+					self.pending_ff = false;
+					self.marker = Some(marker);
+					return Ok(None);
+				}
+				None => return Err(Error::new(ErrorKind::UnexpectedEof, "stream ends after 0xff byte")),
+			}
+		}
+	}
+}
+
+impl<R: Read> Read for ByteStuffedReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> Res<usize> {
+		let mut count_read = 0;
+		for b in buf.iter_mut() {
+			let byte = match self.read_unstuffed() {
+				Ok(Some(byte)) => byte,
+				Ok(None) => break,
+				Err(e) => {
+					if count_read > 0 {
+						break;
+					}
+					return Err(e);
+				}
+			};
+			*b = byte;
+			count_read += 1;
+		}
+		Ok(count_read)
+	}
+}
+
+/**
+	Applies JPEG-style byte stuffing to data written to something implementing [`Write`].
+
+	Every `0xff` byte written will be followed by a stuffed `0x00` byte. Markers can be written without stuffing using `write_marker`.
+
+	To write bits, use this writer as the write target of a `BitWriter`. JPEG requires the last partial byte of a segment to be padded with 1-bits, which can be configured using `BitWriter::set_padding`.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitWriter, ByteStuffedWriter, Padding};
+
+	let mut writer = BEBitWriter::new(ByteStuffedWriter::new(vec![]));
+	writer.set_padding(Padding::Ones);
+	writer.write_bits(0xff, 8).unwrap();
+	writer.write_bits(0x05, 3).unwrap();
+	writer.align().unwrap();
+	writer.get_mut().write_marker(0xd9).unwrap();
+	let vec = writer.into_inner().unwrap().into_inner();
+	assert_eq!(vec, b"\xff\x00\xbf\xff\xd9");
+	```
+
+	[`Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
+*/
+#[derive(Debug)]
+pub struct ByteStuffedWriter<W: Write> {
+	/// Data to write to.
+	inner: W,
+	/// Whether a `0xff` byte has been written, but the stuffed `0x00` byte following it hasn't.
+	pending_zero: bool,
+}
+
+impl<W: Write> ByteStuffedWriter<W> {
+	/// Creates a new `ByteStuffedWriter` writing stuffed data to `inner`.
+	pub fn new(inner: W) -> Self {
+		Self {
+			inner,
+			pending_zero: false,
+		}
+	}
+
+	/// Writes a marker, consisting of a `0xff` byte followed by `marker`, without stuffing.
+	pub fn write_marker(&mut self, marker: u8) -> Res<()> {
+		self.write_pending()?;
+		self.inner.write_all(&[0xff, marker])
+	}
+
+	/// Gets a reference to the underlying writer.
+	pub fn get_ref(&self) -> &W {
+		&self.inner
+	}
+
+	/**
+		Gets a mutable reference to the underlying writer.
+
+		Writing to the underlying writer while a stuffed byte is pending will corrupt the output. Call `flush` first to make sure no stuffed byte is pending.
+	*/
+	pub fn get_mut(&mut self) -> &mut W {
+		&mut self.inner
+	}
+
+	/**
+		Unwraps this `ByteStuffedWriter`, returning the underlying writer.
+
+		A stuffed byte is only left pending if writing it to the underlying writer failed. Call `flush` first to make sure it has been written.
+	*/
+	pub fn into_inner(self) -> W {
+		self.inner
+	}
+
+	fn write_pending(&mut self) -> Res<()> {
+		if self.pending_zero {
+			self.inner.write_all(&[0])?;
+			self.pending_zero = false;
+		}
+		Ok(())
+	}
+}
+
+impl<W: Write> Write for ByteStuffedWriter<W> {
+	fn write(&mut self, buf: &[u8]) -> Res<usize> {
+		self.write_pending()?;
+		match buf.iter().position(|&b| b == 0xff) {
+			Some(0) => {
+				if self.inner.write(&buf[0..1])? == 0 {
+					return Ok(0);
+				}
+				// the byte counts as written from here on, a failed stuffed byte will be retried later
+				self.pending_zero = true;
+				let _ = self.write_pending();
+				Ok(1)
+			}
+			Some(index) => self.inner.write(&buf[0..index]),
+			None => self.inner.write(buf),
+		}
+	}
+
+	fn flush(&mut self) -> Res<()> {
+		self.write_pending()?;
+		self.inner.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Read, Write};
+	use crate::{BEBitReader, BEBitWriter, ByteStuffedReader, ByteStuffedWriter, Padding};
+
+	#[test]
+	fn read_unstuffed() {
+		let mut reader = ByteStuffedReader::new(&b"\x12\xff\x00\x34"[..]);
+		let mut buf = [0; 4];
+		assert_eq!(reader.read(&mut buf).unwrap(), 3);
+		assert_eq!(&buf, b"\x12\xff\x34\0");
+		assert_eq!(reader.marker(), None);
+	}
+
+	#[test]
+	fn read_marker() {
+		let mut reader = ByteStuffedReader::new(&b"\x12\xff\xff\xd0\x34\xff\xd9"[..]);
+		let mut buf = [0; 4];
+		assert_eq!(reader.read(&mut buf).unwrap(), 1);
+		assert_eq!(reader.read(&mut buf).unwrap(), 0);
+		assert_eq!(reader.marker(), Some(0xd0));
+		assert_eq!(reader.resume(), Some(0xd0));
+		assert_eq!(reader.read(&mut buf).unwrap(), 1);
+		assert_eq!(buf[0], 0x34);
+		assert_eq!(reader.read(&mut buf).unwrap(), 0);
+		assert_eq!(reader.marker(), Some(0xd9));
+		assert_eq!(reader.into_inner(), b"");
+	}
+
+	#[test]
+	fn read_truncated() {
+		let mut reader = ByteStuffedReader::new(&b"\xff"[..]);
+		let mut buf = [0; 1];
+		assert!(reader.read(&mut buf).is_err());
+	}
+
+	#[test]
+	fn read_bits_at_marker() {
+		let mut reader = BEBitReader::new(ByteStuffedReader::new(&b"\xa5\xff\x00\xc7\xff\xd9\x00"[..]));
+		assert_eq!(reader.read_bits(4).unwrap(), 0x0a);
+		assert_eq!(reader.read_bits(8).unwrap(), 0x5f);
+		assert_eq!(reader.read_bits(8).unwrap(), 0xfc);
+		assert!(reader.read_bits(8).is_err());
+		assert_eq!(reader.buffered_bits(), 4);
+		assert_eq!(reader.read_bits(4).unwrap(), 0x07);
+		assert_eq!(reader.get_ref().marker(), Some(0xd9));
+		assert_eq!(reader.into_inner().into_inner(), b"\x00");
+	}
+
+	#[test]
+	fn write_stuffed() {
+		let mut writer = ByteStuffedWriter::new(vec![]);
+		writer.write_all(b"\x12\xff\xff\x34").unwrap();
+		writer.write_marker(0xd9).unwrap();
+		assert_eq!(writer.into_inner(), b"\x12\xff\x00\xff\x00\x34\xff\xd9");
+	}
+
+	#[test]
+	fn write_bits_padded() {
+		let mut writer = BEBitWriter::new(ByteStuffedWriter::new(vec![]));
+		writer.set_padding(Padding::Ones);
+		writer.write_bits(0x0f, 4).unwrap();
+		writer.write_bits(0xf0, 8).unwrap();
+		writer.write_bit(false).unwrap();
+		let inner = writer.into_inner().unwrap();
+		assert_eq!(inner.into_inner(), b"\xff\x00\x07");
+	}
+}
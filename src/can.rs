@@ -0,0 +1,419 @@
+use std::io::Result as Res;
+use std::io::{Error, ErrorKind, Read, Write};
+
+use crate::bit_stuffing::{BitDestuffer, BitStuffer, StuffingRule};
+use crate::crc::{Crc, CrcParams};
+use crate::endian::BE;
+use crate::read::BEBitReader;
+use crate::write::BEBitWriter;
+
+/// Identifier of a CAN frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CanId {
+	/// 11-bit identifier of a CAN 2.0A frame.
+	Standard(u16),
+	/// 29-bit identifier of a CAN 2.0B frame.
+	Extended(u32),
+}
+
+/**
+	A classical CAN 2.0 data or remote frame.
+
+	For data frames, `data` must contain as many bytes as indicated by `dlc`, with DLC values above 8 indicating 8 bytes. Remote frames don't carry data, their `dlc` indicates the length of the requested data.
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanFrame {
+	pub id: CanId,
+	/// Whether this is a remote transmission request.
+	pub rtr: bool,
+	/// Data length code.
+	pub dlc: u8,
+	pub data: Vec<u8>,
+}
+
+impl CanFrame {
+	/**
+		Creates a new data frame.
+
+		# Panics
+
+		Panics if `data` is longer than 8 bytes. Use `try_new` for data that isn't known to fit.
+	*/
+	pub fn new(id: CanId, data: &[u8]) -> Self {
+		Self::try_new(id, data).unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// Creates a new data frame like `new`, but returns an error of kind `InvalidInput` instead of panicking if `data` is longer than 8 bytes.
+	pub fn try_new(id: CanId, data: &[u8]) -> Res<Self> {
+		if data.len() > 8 {
+			return Err(Error::new(ErrorKind::InvalidInput, "CAN data longer than 8 bytes"));
+		}
+		Ok(Self {
+			id,
+			rtr: false,
+			dlc: data.len() as u8,
+			data: data.to_vec(),
+		})
+	}
+
+	/// Creates a new remote frame, requesting `dlc` bytes of data.
+	pub fn remote(id: CanId, dlc: u8) -> Self {
+		Self {
+			id,
+			rtr: true,
+			dlc,
+			data: vec![],
+		}
+	}
+
+	/// Returns the number of data bytes indicated by the DLC of a data frame.
+	fn data_len(rtr: bool, dlc: u8) -> usize {
+		if rtr { 0 } else { std::cmp::min(dlc, 8) as usize }
+	}
+
+	fn validate(&self) -> Res<()> {
+		let id_valid = match self.id {
+			CanId::Standard(id) => id <= 0x7ff,
+			CanId::Extended(id) => id <= 0x1fff_ffff,
+		};
+		if !id_valid {
+			return Err(Error::new(ErrorKind::InvalidInput, "CAN identifier out of range"));
+		}
+		if self.dlc > 15 {
+			return Err(Error::new(ErrorKind::InvalidInput, "CAN DLC out of range"));
+		}
+		if self.data.len() != Self::data_len(self.rtr, self.dlc) {
+			return Err(Error::new(ErrorKind::InvalidInput, "CAN data length doesn't match DLC"));
+		}
+		Ok(())
+	}
+}
+
+fn form_error(field: &str) -> Error {
+	Error::new(ErrorKind::InvalidData, format!("form error: {} must be recessive", field))
+}
+
+/**
+	Writes bit-exact CAN 2.0 frames to a `BEBitWriter`.
+
+	Frames are written from the start of frame bit up to and including the end of frame field, with stuffing applied from the start of frame bit to the end of the CRC sequence. The ACK slot is written recessive, as sent by the transmitter. Dominant bits are written as 0, recessive bits as 1.
+
+	Frames are not aligned to byte boundaries. To pad a partial byte at the end with idle (recessive) bits, use `BitWriter::set_padding`.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitWriter, CanFrame, CanId, CanWriter};
+
+	let mut writer = CanWriter::new(BEBitWriter::new(vec![]));
+	writer.write_frame(&CanFrame::new(CanId::Standard(0x123), b"\x42")).unwrap();
+	```
+*/
+#[derive(Debug)]
+pub struct CanWriter<W: Write> {
+	inner: BitStuffer<BE, W>,
+	crc: Crc,
+}
+
+impl<W: Write> CanWriter<W> {
+	/// Creates a new `CanWriter` writing to `inner`.
+	pub fn new(inner: BEBitWriter<W>) -> Self {
+		Self {
+			inner: BitStuffer::new(inner, StuffingRule::CAN),
+			crc: Crc::new(CrcParams::CRC_15_CAN),
+		}
+	}
+
+	/// Gets a reference to the underlying `BitWriter`.
+	pub fn get_ref(&self) -> &BEBitWriter<W> {
+		self.inner.get_ref()
+	}
+
+	/// Gets a mutable reference to the underlying `BitWriter`.
+	pub fn get_mut(&mut self) -> &mut BEBitWriter<W> {
+		self.inner.get_mut()
+	}
+
+	/// Unwraps this `CanWriter`, returning the underlying `BitWriter`.
+	pub fn into_inner(self) -> BEBitWriter<W> {
+		self.inner.into_inner()
+	}
+
+	/// Writes a stuffed field, most significant bit first, updating the CRC.
+	fn write_field(&mut self, value: u32, count: u8) -> Res<()> {
+		for i in (0..count).rev() {
+			let bit = value >> i & 1 != 0;
+			self.crc.update_bit(bit);
+			self.inner.write_bit(bit)?;
+		}
+		Ok(())
+	}
+
+	/**
+		Writes a frame.
+
+		If the frame is invalid, an error of kind `InvalidInput` is returned and nothing is written.
+	*/
+	pub fn write_frame(&mut self, frame: &CanFrame) -> Res<()> {
+		frame.validate()?;
+		self.inner.reset()?;
+		self.crc.reset();
+		self.write_field(0, 1)?; // start of frame
+		match frame.id {
+			CanId::Standard(id) => {
+				self.write_field(id as u32, 11)?;
+				self.write_field(frame.rtr as u32, 1)?;
+				self.write_field(0, 2)?; // IDE, r0
+			}
+			CanId::Extended(id) => {
+				self.write_field(id >> 18, 11)?;
+				self.write_field(0b11, 2)?; // SRR, IDE
+				self.write_field(id & 0x3ffff, 18)?;
+				self.write_field(frame.rtr as u32, 1)?;
+				self.write_field(0, 2)?; // r1, r0
+			}
+		}
+		self.write_field(frame.dlc as u32, 4)?;
+		for &byte in &frame.data {
+			self.write_field(byte as u32, 8)?;
+		}
+		let crc = self.crc.value();
+		for i in (0..15).rev() {
+			self.inner.write_bit(crc >> i & 1 != 0)?;
+		}
+		self.inner.reset()?;
+		// CRC delimiter, ACK slot, ACK delimiter, end of frame
+		self.inner.get_mut().write_bits(0xff, 8)?;
+		self.inner.get_mut().write_bits(0x03, 2)
+	}
+}
+
+/**
+	Reads bit-exact CAN 2.0 frames from a `BEBitReader`.
+
+	Recessive bits before the start of frame bit are skipped as bus idle. Stuffing is removed and validated from the start of frame bit to the end of the CRC sequence. Stuff errors, CRC errors and form errors in the CRC delimiter, ACK delimiter and end of frame field are reported as errors of kind `InvalidData`. The value of the ACK slot is not checked.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitReader, BEBitWriter, CanFrame, CanId, CanReader, CanWriter};
+
+	let frame = CanFrame::new(CanId::Extended(0x1234567), b"\x01\x02");
+	let mut writer = CanWriter::new(BEBitWriter::new(vec![]));
+	writer.write_frame(&frame).unwrap();
+	let vec = writer.into_inner().into_inner().unwrap();
+
+	let mut reader = CanReader::new(BEBitReader::new(&vec[..]));
+	assert_eq!(reader.read_frame().unwrap(), frame);
+	```
+*/
+#[derive(Debug)]
+pub struct CanReader<R: Read> {
+	inner: BitDestuffer<BE, R>,
+	crc: Crc,
+}
+
+impl<R: Read> CanReader<R> {
+	/// Creates a new `CanReader` reading from `inner`.
+	pub fn new(inner: BEBitReader<R>) -> Self {
+		Self {
+			inner: BitDestuffer::new(inner, StuffingRule::CAN),
+			crc: Crc::new(CrcParams::CRC_15_CAN),
+		}
+	}
+
+	/// Gets a reference to the underlying `BitReader`.
+	pub fn get_ref(&self) -> &BEBitReader<R> {
+		self.inner.get_ref()
+	}
+
+	/// Gets a mutable reference to the underlying `BitReader`.
+	pub fn get_mut(&mut self) -> &mut BEBitReader<R> {
+		self.inner.get_mut()
+	}
+
+	/// Unwraps this `CanReader`, returning the underlying `BitReader`.
+	pub fn into_inner(self) -> BEBitReader<R> {
+		self.inner.into_inner()
+	}
+
+	/// Reads a stuffed field, most significant bit first, updating the CRC.
+	fn read_field(&mut self, count: u8) -> Res<u32> {
+		let mut value = 0;
+		for _ in 0..count {
+			let bit = self.inner.read_bit()?;
+			self.crc.update_bit(bit);
+			value = value << 1 | bit as u32;
+		}
+		Ok(value)
+	}
+
+	fn expect_recessive(&mut self, count: u8, field: &str) -> Res<()> {
+		for _ in 0..count {
+			if !self.inner.get_mut().read_bit()? {
+				return Err(form_error(field));
+			}
+		}
+		Ok(())
+	}
+
+	/// Reads the next frame.
+	pub fn read_frame(&mut self) -> Res<CanFrame> {
+		// skip bus idle until the start of frame bit
+		loop {
+			self.inner.reset();
+			if !self.inner.read_bit()? {
+				break;
+			}
+		}
+		self.crc.reset();
+		self.crc.update_bit(false);
+		let id_a = self.read_field(11)?;
+		let srr_rtr = self.read_field(1)? != 0;
+		let ide = self.read_field(1)? != 0;
+		if ide && !srr_rtr {
+			return Err(form_error("SRR"));
+		}
+		let (id, rtr) = if ide {
+			let id_b = self.read_field(18)?;
+			let rtr = self.read_field(1)? != 0;
+			self.read_field(2)?; // r1, r0
+			(CanId::Extended(id_a << 18 | id_b), rtr)
+		} else {
+			self.read_field(1)?; // r0
+			(CanId::Standard(id_a as u16), srr_rtr)
+		};
+		let dlc = self.read_field(4)? as u8;
+		let mut data = Vec::with_capacity(8);
+		for _ in 0..CanFrame::data_len(rtr, dlc) {
+			data.push(self.read_field(8)? as u8);
+		}
+		let crc = self.crc.value();
+		let mut received_crc = 0;
+		for _ in 0..15 {
+			received_crc = received_crc << 1 | self.inner.read_bit()? as u64;
+		}
+		self.inner.read_pending()?;
+		self.inner.reset();
+		if received_crc != crc {
+			return Err(Error::new(ErrorKind::InvalidData, format!("CRC error: expected {:#06x}, received {:#06x}", crc, received_crc)));
+		}
+		self.expect_recessive(1, "CRC delimiter")?;
+		self.inner.get_mut().read_bit()?; // ACK slot
+		self.expect_recessive(1, "ACK delimiter")?;
+		self.expect_recessive(7, "end of frame")?;
+		Ok(CanFrame { id, rtr, dlc, data })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{ErrorKind, Read};
+	use crate::{BEBitReader, BEBitWriter, CanFrame, CanId, CanReader, CanWriter, Padding};
+	use crate::test_util::ErrorReader;
+
+	fn encode(frame: &CanFrame) -> Vec<u8> {
+		let mut writer = CanWriter::new(BEBitWriter::new(vec![]));
+		writer.write_frame(frame).unwrap();
+		let mut writer = writer.into_inner();
+		writer.set_padding(Padding::Ones);
+		writer.into_inner().unwrap()
+	}
+
+	#[test]
+	fn write_standard() {
+		let frame = CanFrame::new(CanId::Standard(0x7ff), b"");
+		// 0 11111[0]11111[0]1 000 00[1]00 010011100101111 1111111111
+		assert_eq!(encode(&frame), b"\x7d\xf4\x11\x39\x7f\xff");
+	}
+
+	#[test]
+	fn roundtrip() {
+		let frames = [
+			CanFrame::new(CanId::Standard(0x000), b""),
+			CanFrame::new(CanId::Standard(0x123), b"\x00\x00\x00\x00\xff\xff\xff\xff"),
+			CanFrame::new(CanId::Extended(0x1fff_ffff), b"\x55"),
+			CanFrame::remote(CanId::Standard(0x456), 8),
+			CanFrame::remote(CanId::Extended(0x0000_0001), 0),
+		];
+		let mut writer = CanWriter::new(BEBitWriter::new(vec![]));
+		for frame in &frames {
+			writer.write_frame(frame).unwrap();
+			writer.get_mut().write_bits(0x07, 3).unwrap(); // intermission
+		}
+		let mut writer = writer.into_inner();
+		writer.set_padding(Padding::Ones);
+		let vec = writer.into_inner().unwrap();
+		let mut reader = CanReader::new(BEBitReader::new(&vec[..]));
+		for frame in &frames {
+			assert_eq!(&reader.read_frame().unwrap(), frame);
+		}
+	}
+
+	#[test]
+	fn stuffed_bit_after_crc_would_block() {
+		let vec = encode(&CanFrame::new(CanId::Standard(0x7fe), b"\xff"));
+		// the CRC ends with five identical bits at the end of byte 5, so the stuffed bit after it is in byte 6
+		let inner = (&vec[..6]).chain(ErrorReader::new(&vec[6..], ErrorKind::WouldBlock));
+		let mut reader = CanReader::new(BEBitReader::new(inner));
+		assert_eq!(reader.read_frame().unwrap_err().kind(), ErrorKind::WouldBlock);
+	}
+
+	#[test]
+	fn try_new() {
+		assert_eq!(CanFrame::try_new(CanId::Standard(0x123), &[0; 9]).unwrap_err().kind(), ErrorKind::InvalidInput);
+		assert_eq!(CanFrame::try_new(CanId::Standard(0x123), &[0; 8]).unwrap(), CanFrame::new(CanId::Standard(0x123), &[0; 8]));
+	}
+
+	#[test]
+	fn invalid_frame() {
+		let mut writer = CanWriter::new(BEBitWriter::new(vec![]));
+		let frame = CanFrame { id: CanId::Standard(0x800), rtr: false, dlc: 0, data: vec![] };
+		assert_eq!(writer.write_frame(&frame).unwrap_err().kind(), ErrorKind::InvalidInput);
+		let frame = CanFrame { id: CanId::Standard(0x100), rtr: false, dlc: 2, data: vec![0] };
+		assert_eq!(writer.write_frame(&frame).unwrap_err().kind(), ErrorKind::InvalidInput);
+		assert!(writer.get_ref().is_aligned());
+	}
+
+	#[test]
+	fn stuff_error() {
+		let mut vec = encode(&CanFrame::new(CanId::Standard(0x7ff), b""));
+		// replace the stuffed 0-bit after the first five identifier bits
+		vec[0] |= 0x02;
+		let mut reader = CanReader::new(BEBitReader::new(&vec[..]));
+		let err = reader.read_frame().unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidData);
+		assert!(err.to_string().contains("stuff error"));
+	}
+
+	#[test]
+	fn crc_error() {
+		let mut vec = encode(&CanFrame::new(CanId::Standard(0x123), b"\x12"));
+		vec[3] ^= 0x10;
+		let mut reader = CanReader::new(BEBitReader::new(&vec[..]));
+		let err = reader.read_frame().unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidData);
+		assert!(err.to_string().contains("CRC error"));
+	}
+
+	#[test]
+	fn srr_dominant() {
+		let mut vec = encode(&CanFrame::new(CanId::Extended(0x1555_5555), b""));
+		// 0 10101010101 1 1 …, clear the SRR bit
+		vec[1] &= !0x08;
+		let mut reader = CanReader::new(BEBitReader::new(&vec[..]));
+		let err = reader.read_frame().unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidData);
+		assert_eq!(err.to_string(), "form error: SRR must be recessive");
+	}
+
+	#[test]
+	fn form_error() {
+		let mut vec = encode(&CanFrame::new(CanId::Standard(0x7ff), b""));
+		vec[5] &= 0x7f;
+		let mut reader = CanReader::new(BEBitReader::new(&vec[..]));
+		let err = reader.read_frame().unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidData);
+		assert!(err.to_string().contains("form error"));
+	}
+}
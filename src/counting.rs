@@ -0,0 +1,140 @@
+use std::io::Result as Res;
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
+
+/**
+	A write target that only counts the bytes written to it, for measuring the encoded size of data with a `BitWriter` without storing it.
+
+	Supports reading and seeking like `std::io::Cursor<Vec<u8>>`, so the same encoding code, including placeholders filled with `BitWriter::fill_placeholder`, can be run with a `CountingSink` to measure the size and with a real target to write the data. Since the data isn't stored, reading returns 0-bytes up to the length.
+
+	The size in bits is available from `BitWriter::bit_position` or, excluding padding, from `BitWriter::finish`.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitWriter, CountingSink};
+	use std::io::{Cursor, Read, Result, Seek, Write};
+
+	fn encode<W: Read + Write + Seek>(writer: &mut BEBitWriter<W>, values: &[u8]) -> Result<()> {
+		let count = writer.reserve_bits(4)?;
+		for &value in values {
+			writer.write_bits(value, 3)?;
+		}
+		writer.fill_placeholder(count, |writer| writer.write_bits_checked(values.len() as u8, 4))
+	}
+
+	let values = [1, 2, 3, 4, 5];
+	let mut counter = BEBitWriter::new(CountingSink::new());
+	encode(&mut counter, &values).unwrap();
+	assert_eq!(counter.bit_position(), 19);
+	let (sink, _) = counter.finish().unwrap();
+	assert_eq!(sink.len(), 3);
+
+	let mut writer = BEBitWriter::new(Cursor::new(Vec::with_capacity(sink.len() as usize)));
+	encode(&mut writer, &values).unwrap();
+	assert_eq!(writer.into_inner().unwrap().into_inner(), b"\x52\x9c\xa0");
+	```
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CountingSink {
+	position: u64,
+	len: u64,
+}
+
+impl CountingSink {
+	/// Creates a new, empty `CountingSink`.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Returns the length of the data in bytes, i.e. the furthest position written to.
+	pub fn len(&self) -> u64 {
+		self.len
+	}
+
+	/// Returns whether nothing has been written.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Returns the current position in bytes.
+	pub fn position(&self) -> u64 {
+		self.position
+	}
+}
+
+impl Write for CountingSink {
+	fn write(&mut self, buf: &[u8]) -> Res<usize> {
+		self.position = self.position.checked_add(buf.len() as u64).ok_or_else(|| Error::new(ErrorKind::InvalidInput, "write past the maximum position"))?;
+		self.len = std::cmp::max(self.len, self.position);
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> Res<()> {
+		Ok(())
+	}
+}
+
+/// Reads 0-bytes up to the length, as the data isn't stored.
+impl Read for CountingSink {
+	fn read(&mut self, buf: &mut [u8]) -> Res<usize> {
+		let len = std::cmp::min(buf.len() as u64, self.len.saturating_sub(self.position)) as usize;
+		buf[..len].iter_mut().for_each(|b| *b = 0);
+		self.position += len as u64;
+		Ok(len)
+	}
+}
+
+impl Seek for CountingSink {
+	fn seek(&mut self, pos: SeekFrom) -> Res<u64> {
+		let position = match pos {
+			SeekFrom::Start(offset) => Some(offset),
+			SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+			SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+		};
+		self.position = position.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))?;
+		Ok(self.position)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+	use crate::{BEBitWriter, CountingSink, LEBitWriter, Padding};
+
+	#[test]
+	fn sink() {
+		let mut sink = CountingSink::new();
+		assert!(sink.is_empty());
+		sink.write_all(b"\x12\x34\x56").unwrap();
+		assert_eq!(sink.seek(SeekFrom::Current(-2)).unwrap(), 1);
+		let mut buf = [0xff; 4];
+		assert_eq!(sink.read(&mut buf).unwrap(), 2);
+		assert_eq!(buf, [0, 0, 0xff, 0xff]);
+		assert_eq!(sink.seek(SeekFrom::End(2)).unwrap(), 5);
+		assert_eq!(sink.read(&mut buf).unwrap(), 0);
+		sink.write_all(b"\x78").unwrap();
+		assert_eq!((sink.len(), sink.position()), (6, 6));
+		assert_eq!(sink.seek(SeekFrom::Current(-7)).unwrap_err().kind(), ErrorKind::InvalidInput);
+		sink.seek(SeekFrom::Start(u64::MAX)).unwrap();
+		assert_eq!(sink.write(b"\x00").unwrap_err().kind(), ErrorKind::InvalidInput);
+	}
+
+	#[test]
+	fn measure() {
+		let mut counter = LEBitWriter::new(CountingSink::new());
+		counter.set_padding(Padding::StopBit);
+		counter.write_bits(0x05, 3).unwrap();
+		let length = counter.reserve_bits(12).unwrap();
+		counter.write_all(b"\x12\x34").unwrap();
+		counter.fill_placeholder(length, |writer| {
+			writer.write_bits(0xff, 8)?;
+			writer.write_bits(0x0f, 4)
+		}).unwrap();
+		assert_eq!(counter.bit_position(), 31);
+		let (sink, bit_count) = counter.finish().unwrap();
+		assert_eq!((sink.len(), bit_count), (4, 31));
+		let mut counter = BEBitWriter::new(CountingSink::new());
+		counter.write_bit(true).unwrap();
+		assert_eq!(counter.into_inner().unwrap().len(), 1);
+	}
+}
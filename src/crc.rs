@@ -0,0 +1,431 @@
+use std::io::Result as Res;
+use std::io::{Error, ErrorKind, Read, Write};
+
+use crate::endian::BitEndianness;
+use crate::read::{BitRead, BitReader};
+use crate::write::{BitWrite, BitWriter};
+
+/**
+	Parameters of a CRC algorithm, following the Rocksoft model.
+
+	`poly` and `init` are given in non-reflected form, without the implicit top bit of the polynomial.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CrcParams {
+	/// Width of the CRC in bits, 1 <= width <= 64.
+	pub width: u8,
+	/// Generator polynomial.
+	pub poly: u64,
+	/// Initial value of the CRC register.
+	pub init: u64,
+	/// Whether bytes are processed least significant bit first by `Crc::update_bytes`.
+	pub refin: bool,
+	/// Whether the CRC register is reflected before the final XOR.
+	pub refout: bool,
+	/// Value XORed into the final CRC.
+	pub xorout: u64,
+}
+
+impl CrcParams {
+	/// CRC-5 of USB token packets.
+	pub const CRC_5_USB: Self = Self { width: 5, poly: 0x05, init: 0x1f, refin: true, refout: true, xorout: 0x1f };
+	/// CRC-15 of CAN frames.
+	pub const CRC_15_CAN: Self = Self { width: 15, poly: 0x4599, init: 0, refin: false, refout: false, xorout: 0 };
+	/// CRC-16 frame check sequence of HDLC, also known as CRC-16/X-25.
+	pub const CRC_16_IBM_SDLC: Self = Self { width: 16, poly: 0x1021, init: 0xffff, refin: true, refout: true, xorout: 0xffff };
+	/// CRC-24 parity of Mode S transponder messages.
+	pub const CRC_24_MODE_S: Self = Self { width: 24, poly: 0xfff409, init: 0, refin: false, refout: false, xorout: 0 };
+	/// CRC-32 of Ethernet, zlib and others.
+	pub const CRC_32_ISO_HDLC: Self = Self { width: 32, poly: 0x04c1_1db7, init: 0xffff_ffff, refin: true, refout: true, xorout: 0xffff_ffff };
+}
+
+/**
+	Computes a CRC over an arbitrary number of bits.
+
+	Bits are processed one at a time, so CRCs over fields that aren't a multiple of 8 bits in size can be computed. To compute a CRC over the bits read from a `BitReader` or written to a `BitWriter`, use `CrcReader` or `CrcWriter`.
+
+	# Examples
+
+	```
+	use endio_bit::{Crc, CrcParams};
+
+	let mut crc = Crc::new(CrcParams::CRC_32_ISO_HDLC);
+	crc.update_bytes(b"123456789");
+	assert_eq!(crc.value(), 0xcbf43926);
+	```
+*/
+#[derive(Clone, Debug)]
+pub struct Crc {
+	params: CrcParams,
+	/// Non-reflected CRC register.
+	register: u64,
+}
+
+impl Crc {
+	/**
+		Creates a new `Crc` computing the CRC with the specified parameters.
+
+		# Panics
+
+		Panics if the width is 0 or greater than 64. Use `try_new` for parameters that aren't known to be valid.
+	*/
+	pub fn new(params: CrcParams) -> Self {
+		Self::try_new(params).unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// Creates a new `Crc` like `new`, but returns an error of kind `InvalidInput` instead of panicking if the width is 0 or greater than 64.
+	pub fn try_new(params: CrcParams) -> Res<Self> {
+		if params.width == 0 || params.width > 64 {
+			return Err(Error::new(ErrorKind::InvalidInput, "CRC width must be between 1 and 64"));
+		}
+		Ok(Self {
+			params,
+			register: params.init & Self::mask(params.width),
+		})
+	}
+
+	fn mask(width: u8) -> u64 {
+		u64::MAX >> (64 - width)
+	}
+
+	/// Returns the parameters of this CRC.
+	pub fn params(&self) -> &CrcParams {
+		&self.params
+	}
+
+	/// Resets the CRC to its initial value.
+	pub fn reset(&mut self) {
+		self.register = self.params.init & Self::mask(self.params.width);
+	}
+
+	/// Updates the CRC with a single bit.
+	pub fn update_bit(&mut self, bit: bool) {
+		let width = self.params.width;
+		let feedback = bit ^ (self.register >> (width - 1) & 1 != 0);
+		self.register = (self.register << 1) & Self::mask(width);
+		if feedback {
+			self.register ^= self.params.poly & Self::mask(width);
+		}
+	}
+
+	/// Updates the CRC with bytes, processing each byte in the bit order specified by `refin`.
+	pub fn update_bytes(&mut self, bytes: &[u8]) {
+		for &byte in bytes {
+			for i in 0..8 {
+				let shift = if self.params.refin { i } else { 7 - i };
+				self.update_bit(byte >> shift & 1 != 0);
+			}
+		}
+	}
+
+	/// Returns the CRC of the bits processed since creation or the last reset.
+	pub fn value(&self) -> u64 {
+		let mut value = self.register;
+		if self.params.refout {
+			value = value.reverse_bits() >> (64 - self.params.width);
+		}
+		value ^ (self.params.xorout & Self::mask(self.params.width))
+	}
+
+	/// Returns the CRC of the bits processed since creation or the last checkpoint, and resets it.
+	pub fn checkpoint(&mut self) -> u64 {
+		let value = self.value();
+		self.reset();
+		value
+	}
+}
+
+/**
+	Computes a CRC over the bits read from a `BitReader`.
+
+	Every bit read, through the bit-level methods as well as through the `Read` implementation, updates the CRC in the order the bits appear in the stream. The bit endianness of the reader therefore determines the bit order of the CRC input; `refin` is not applied.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitReader, CrcParams, CrcReader};
+
+	let mut reader = CrcReader::new(BEBitReader::new(&b"\x8b\x40"[..]), CrcParams::CRC_15_CAN);
+	reader.read_bits(8).unwrap();
+	reader.read_bits(2).unwrap();
+	let crc = reader.checkpoint();
+	```
+*/
+#[derive(Debug)]
+pub struct CrcReader<E: BitEndianness, R: Read> {
+	inner: BitReader<E, R>,
+	crc: Crc,
+}
+
+impl<E: BitEndianness, R: Read> CrcReader<E, R> {
+	/// Creates a new `CrcReader` reading from `inner`, computing the CRC with the specified parameters.
+	pub fn new(inner: BitReader<E, R>, params: CrcParams) -> Self {
+		Self {
+			inner,
+			crc: Crc::new(params),
+		}
+	}
+
+	/// Gets a reference to the CRC of the bits read since creation or the last checkpoint.
+	pub fn crc(&self) -> &Crc {
+		&self.crc
+	}
+
+	/// Returns the CRC of the bits read since creation or the last checkpoint, and starts a new one.
+	pub fn checkpoint(&mut self) -> u64 {
+		self.crc.checkpoint()
+	}
+
+	/// Gets a reference to the underlying `BitReader`.
+	pub fn get_ref(&self) -> &BitReader<E, R> {
+		&self.inner
+	}
+
+	/**
+		Gets a mutable reference to the underlying `BitReader`.
+
+		Bits read directly from the underlying reader don't update the CRC.
+	*/
+	pub fn get_mut(&mut self) -> &mut BitReader<E, R> {
+		&mut self.inner
+	}
+
+	/// Unwraps this `CrcReader`, returning the underlying `BitReader`.
+	pub fn into_inner(self) -> BitReader<E, R> {
+		self.inner
+	}
+
+	/// Reads a single bit, with the same semantics as `BitReader::read_bit`.
+	pub fn read_bit(&mut self) -> Res<bool> {
+		let bit = self.inner.read_bit()?;
+		self.crc.update_bit(bit);
+		Ok(bit)
+	}
+
+	/// Reads 8 bits or less, with the same semantics as `BitReader::read_bits`.
+	pub fn read_bits(&mut self, count: u8) -> Res<u8> {
+		let bits = self.inner.read_bits(count)?;
+		for i in 0..count {
+			self.crc.update_bit(bits & E::nth_bit_mask(i, count) != 0);
+		}
+		Ok(bits)
+	}
+}
+
+impl<E: BitEndianness, R: Read> BitRead for CrcReader<E, R> {
+	fn read_bit(&mut self) -> Res<bool> {
+		CrcReader::read_bit(self)
+	}
+
+	fn msb_first(&self) -> bool {
+		E::msb_first()
+	}
+
+	fn read_bits(&mut self, count: u8) -> Res<u8> {
+		CrcReader::read_bits(self, count)
+	}
+}
+
+impl<E: BitEndianness, R: Read> Read for CrcReader<E, R> {
+	fn read(&mut self, buf: &mut [u8]) -> Res<usize> {
+		let count_read = self.inner.read(buf)?;
+		for &byte in &buf[..count_read] {
+			for i in 0..8 {
+				self.crc.update_bit(byte & E::nth_bit_mask(i, 8) != 0);
+			}
+		}
+		Ok(count_read)
+	}
+}
+
+/**
+	Computes a CRC over the bits written to a `BitWriter`.
+
+	Every bit written, through the bit-level methods as well as through the `Write` implementation, updates the CRC in the order the bits appear in the stream. The bit endianness of the writer therefore determines the bit order of the CRC input; `refin` is not applied.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitWriter, CrcParams, CrcWriter};
+
+	let mut writer = CrcWriter::new(BEBitWriter::new(vec![]), CrcParams::CRC_5_USB);
+	writer.write_bits(0x10, 5).unwrap();
+	let crc = writer.checkpoint();
+	writer.write_bits(crc as u8, 5).unwrap();
+	```
+*/
+#[derive(Debug)]
+pub struct CrcWriter<E: BitEndianness, W: Write> {
+	inner: BitWriter<E, W>,
+	crc: Crc,
+}
+
+impl<E: BitEndianness, W: Write> CrcWriter<E, W> {
+	/// Creates a new `CrcWriter` writing to `inner`, computing the CRC with the specified parameters.
+	pub fn new(inner: BitWriter<E, W>, params: CrcParams) -> Self {
+		Self {
+			inner,
+			crc: Crc::new(params),
+		}
+	}
+
+	/// Gets a reference to the CRC of the bits written since creation or the last checkpoint.
+	pub fn crc(&self) -> &Crc {
+		&self.crc
+	}
+
+	/// Returns the CRC of the bits written since creation or the last checkpoint, and starts a new one.
+	pub fn checkpoint(&mut self) -> u64 {
+		self.crc.checkpoint()
+	}
+
+	/// Gets a reference to the underlying `BitWriter`.
+	pub fn get_ref(&self) -> &BitWriter<E, W> {
+		&self.inner
+	}
+
+	/**
+		Gets a mutable reference to the underlying `BitWriter`.
+
+		Bits written directly to the underlying writer don't update the CRC.
+	*/
+	pub fn get_mut(&mut self) -> &mut BitWriter<E, W> {
+		&mut self.inner
+	}
+
+	/// Unwraps this `CrcWriter`, returning the underlying `BitWriter`.
+	pub fn into_inner(self) -> BitWriter<E, W> {
+		self.inner
+	}
+
+	/// Writes a single bit, with the same semantics as `BitWriter::write_bit`.
+	pub fn write_bit(&mut self, bit: bool) -> Res<()> {
+		self.inner.write_bit(bit)?;
+		self.crc.update_bit(bit);
+		Ok(())
+	}
+
+	/// Writes 8 bits or less, with the same semantics as `BitWriter::write_bits`.
+	pub fn write_bits(&mut self, bits: u8, count: u8) -> Res<()> {
+		self.inner.write_bits(bits, count)?;
+		for i in 0..count {
+			self.crc.update_bit(bits & E::nth_bit_mask(i, count) != 0);
+		}
+		Ok(())
+	}
+}
+
+impl<E: BitEndianness, W: Write> BitWrite for CrcWriter<E, W> {
+	fn write_bit(&mut self, bit: bool) -> Res<()> {
+		CrcWriter::write_bit(self, bit)
+	}
+
+	fn msb_first(&self) -> bool {
+		E::msb_first()
+	}
+
+	fn write_bits(&mut self, bits: u8, count: u8) -> Res<()> {
+		CrcWriter::write_bits(self, bits, count)
+	}
+}
+
+impl<E: BitEndianness, W: Write> Write for CrcWriter<E, W> {
+	fn write(&mut self, buf: &[u8]) -> Res<usize> {
+		let count_written = self.inner.write(buf)?;
+		for &byte in &buf[..count_written] {
+			for i in 0..8 {
+				self.crc.update_bit(byte & E::nth_bit_mask(i, 8) != 0);
+			}
+		}
+		Ok(count_written)
+	}
+
+	fn flush(&mut self) -> Res<()> {
+		self.inner.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{ErrorKind, Read, Write};
+	use crate::{BEBitReader, BEBitWriter, LEBitReader, LEBitWriter, Crc, CrcParams, CrcReader, CrcWriter};
+
+	fn check(params: CrcParams) -> u64 {
+		let mut crc = Crc::new(params);
+		crc.update_bytes(b"123456789");
+		crc.value()
+	}
+
+	#[test]
+	fn check_values() {
+		assert_eq!(check(CrcParams::CRC_5_USB), 0x19);
+		assert_eq!(check(CrcParams::CRC_15_CAN), 0x059e);
+		assert_eq!(check(CrcParams::CRC_16_IBM_SDLC), 0x906e);
+		assert_eq!(check(CrcParams::CRC_32_ISO_HDLC), 0xcbf4_3926);
+		let crc_64_xz = CrcParams { width: 64, poly: 0x42f0_e1eb_a9ea_3693, init: u64::MAX, refin: true, refout: true, xorout: u64::MAX };
+		assert_eq!(check(crc_64_xz), 0x995d_c9bb_df19_39fa);
+	}
+
+	#[test]
+	fn try_new() {
+		let params = CrcParams { width: 0, ..CrcParams::CRC_5_USB };
+		assert_eq!(Crc::try_new(params).unwrap_err().kind(), ErrorKind::InvalidInput);
+		let params = CrcParams { width: 65, ..CrcParams::CRC_5_USB };
+		assert_eq!(Crc::try_new(params).unwrap_err().kind(), ErrorKind::InvalidInput);
+		assert_eq!(Crc::try_new(CrcParams::CRC_5_USB).unwrap().value(), Crc::new(CrcParams::CRC_5_USB).value());
+	}
+
+	#[test]
+	fn checkpoint() {
+		let mut crc = Crc::new(CrcParams::CRC_32_ISO_HDLC);
+		crc.update_bytes(b"1234");
+		crc.checkpoint();
+		crc.update_bytes(b"123456789");
+		assert_eq!(crc.checkpoint(), 0xcbf4_3926);
+		assert_eq!(crc.value(), 0);
+	}
+
+	#[test]
+	fn reader_be() {
+		let mut reader = CrcReader::new(BEBitReader::new(&b"123456789"[..]), CrcParams::CRC_15_CAN);
+		assert_eq!(reader.read_bit().unwrap(), false);
+		assert_eq!(reader.read_bits(3).unwrap(), 0x03);
+		let mut buf = [0; 8];
+		reader.read_exact(&mut buf).unwrap();
+		assert_eq!(reader.read_bits(4).unwrap(), 0x09);
+		assert_eq!(reader.crc().value(), 0x059e);
+	}
+
+	#[test]
+	fn reader_le() {
+		let mut reader = CrcReader::new(LEBitReader::new(&b"123456789"[..]), CrcParams::CRC_32_ISO_HDLC);
+		let mut buf = [0; 9];
+		reader.read_exact(&mut buf).unwrap();
+		assert_eq!(reader.checkpoint(), 0xcbf4_3926);
+	}
+
+	#[test]
+	fn writer_be() {
+		let data = b"123456789";
+		let shifted: Vec<u8> = data.windows(2).map(|w| w[0] << 4 | w[1] >> 4).collect();
+		let mut writer = CrcWriter::new(BEBitWriter::new(vec![]), CrcParams::CRC_15_CAN);
+		writer.write_bits(data[0] >> 4, 4).unwrap();
+		writer.write_all(&shifted).unwrap();
+		writer.write_bits(data[8], 4).unwrap();
+		assert_eq!(writer.checkpoint(), 0x059e);
+		assert_eq!(writer.into_inner().into_inner().unwrap(), data);
+	}
+
+	#[test]
+	fn writer_le() {
+		// USB token with address 0x15 and endpoint 0xe, transmitted least significant bit first
+		let mut writer = CrcWriter::new(LEBitWriter::new(vec![]), CrcParams::CRC_5_USB);
+		writer.write_bits(0x15, 7).unwrap();
+		writer.write_bits(0x0e, 4).unwrap();
+		let crc = writer.checkpoint();
+		assert_eq!(crc, 0x1d);
+		writer.write_bits(crc as u8, 5).unwrap();
+		// the USB specification lists the CRC as 10111 in transmission order
+		assert_eq!(writer.into_inner().into_inner().unwrap(), b"\x15\xef");
+	}
+}
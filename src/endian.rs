@@ -1,19 +1,44 @@
 /**
 	Specifies the bit endianness of a `BitReader` or `BitWriter`.
 
-	You can't implement this trait, it only exists as a trait bound.
+	You can't implement this trait, it only exists as a trait bound, for code that works with either bit endianness.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitReader, BitEndianness, BitReader, LEBitReader};
+	use std::io::{Read, Result};
+
+	fn parse_flag<E: BitEndianness, R: Read>(reader: &mut BitReader<E, R>) -> Result<bool> {
+		reader.read_bit()
+	}
+
+	assert_eq!(parse_flag(&mut BEBitReader::new(&b"\x80"[..])).unwrap(), true);
+	assert_eq!(parse_flag(&mut LEBitReader::new(&b"\x80"[..])).unwrap(), false);
+	```
 */
 pub trait BitEndianness: private::Sealed {
 	/// Shifts towards the most significant bit.
+	#[doc(hidden)]
 	fn shift_msb(val: u8, by: u8) -> u8;
 	/// Shifts towards the least significant bit.
+	#[doc(hidden)]
 	fn shift_lsb(val: u8, by: u8) -> u8;
 	/// Aligns right.
+	#[doc(hidden)]
 	fn align_right(val: u8, count: u8) -> u8;
+	/// Returns the mask of the `index`th bit of a `count`-bit value, counted in reading/writing order.
+	#[doc(hidden)]
+	fn nth_bit_mask(index: u8, count: u8) -> u8;
+	/// Returns whether the most significant bit of a byte comes first.
+	#[doc(hidden)]
+	fn msb_first() -> bool;
 }
 
+/// Big endian bit numbering: the most significant bit of a byte comes first.
 #[derive(Debug)]
 pub struct BigEndian;
+/// Little endian bit numbering: the least significant bit of a byte comes first.
 #[derive(Debug)]
 pub struct LittleEndian;
 
@@ -21,14 +46,20 @@ impl BitEndianness for BigEndian {
 	fn shift_msb(val: u8, by: u8) -> u8 { val << by }
 	fn shift_lsb(val: u8, by: u8) -> u8 { val >> by }
 	fn align_right(val: u8, _count: u8) -> u8 { val }
+	fn nth_bit_mask(index: u8, count: u8) -> u8 { 1 << (count - 1 - index) }
+	fn msb_first() -> bool { true }
 }
 impl BitEndianness for LittleEndian {
 	fn shift_msb(val: u8, by: u8) -> u8 { val >> by }
 	fn shift_lsb(val: u8, by: u8) -> u8 { val << by }
 	fn align_right(val: u8, count: u8) -> u8 { Self::shift_msb(val, 8 - count) }
+	fn nth_bit_mask(index: u8, _count: u8) -> u8 { 1 << index }
+	fn msb_first() -> bool { false }
 }
 
+/// Shorthand for `BigEndian`.
 pub type BE = BigEndian;
+/// Shorthand for `LittleEndian`.
 pub type LE = LittleEndian;
 
 // ensures no one else implements the trait
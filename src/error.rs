@@ -0,0 +1,180 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+
+/**
+	An error of a bit-level operation of a `BitReader` or `BitWriter`, with the position in the stream at which it occurred.
+
+	Bit-level operations return their errors as [`std::io::Error`] with the same kind as the underlying error, wrapping a `BitError`. Use `BitError::from_io_error` to access it. The `Read` and `Write` implementations return the errors of the underlying reader or writer unchanged.
+
+	The context labels pushed with `push_context` or `with_context` at the time of the error are included, so that errors read like "unexpected end of file at bit 1043 while reading header.flags".
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitReader, BitError};
+
+	let mut reader = BEBitReader::new(&b"\x47"[..]);
+	let err = reader.with_context("header", |reader| {
+		reader.read_bits(5)?;
+		reader.with_context("flags", |reader| reader.read_bits(4))
+	}).unwrap_err();
+	assert_eq!(err.to_string(), "unexpected end of file at bit 5 while reading header.flags");
+	let bit_error = BitError::from_io_error(&err).unwrap();
+	assert_eq!(bit_error.position(), 5);
+	assert_eq!(bit_error.count(), 4);
+	```
+
+	[`std::io::Error`]: https://doc.rust-lang.org/std/io/struct.Error.html
+*/
+#[derive(Debug)]
+pub struct BitError {
+	position: u64,
+	count: u32,
+	context: Vec<Cow<'static, str>>,
+	writing: bool,
+	error: Error,
+}
+
+impl BitError {
+	pub(crate) fn new(position: u64, count: u32, context: &[Cow<'static, str>], writing: bool, error: Error) -> Self {
+		Self {
+			position,
+			count,
+			context: context.to_vec(),
+			writing,
+			error,
+		}
+	}
+
+	/// Returns the `BitError` wrapped by an `std::io::Error` returned from a bit-level operation, if any.
+	pub fn from_io_error(error: &Error) -> Option<&Self> {
+		error.get_ref().and_then(|e| e.downcast_ref())
+	}
+
+	/// Returns the position in bits from the start of the stream at which the error occurred.
+	pub fn position(&self) -> u64 {
+		self.position
+	}
+
+	/// Returns the number of bits the failed operation was requested to read or write.
+	pub fn count(&self) -> u32 {
+		self.count
+	}
+
+	/// Returns the context labels at the time of the error, outermost first.
+	pub fn context(&self) -> &[Cow<'static, str>] {
+		&self.context
+	}
+
+	/// Returns the kind of the underlying error.
+	pub fn kind(&self) -> ErrorKind {
+		self.error.kind()
+	}
+
+	/// Gets a reference to the underlying error.
+	pub fn io_error(&self) -> &Error {
+		&self.error
+	}
+
+	/// Unwraps this `BitError`, returning the underlying error.
+	pub fn into_io_error(self) -> Error {
+		self.error
+	}
+}
+
+impl fmt::Display for BitError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let verb = if self.writing { "writing" } else { "reading" };
+		write!(f, "{} at bit {} while {} ", self.error, self.position, verb)?;
+		if self.context.is_empty() {
+			write!(f, "{} bit{}", self.count, if self.count == 1 { "" } else { "s" })
+		} else {
+			write!(f, "{}", self.context.join("."))
+		}
+	}
+}
+
+impl std::error::Error for BitError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(&self.error)
+	}
+}
+
+/// Wraps the `BitError` in an `std::io::Error` of the same kind.
+impl From<BitError> for Error {
+	fn from(error: BitError) -> Self {
+		Error::new(error.kind(), error)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Error, ErrorKind};
+	use crate::{BEBitReader, BEBitWriter, BitError};
+
+	#[test]
+	fn read_eof() {
+		let mut reader = BEBitReader::new(&b"\xff"[..]);
+		reader.read_bits(7).unwrap();
+		let err = reader.read_bits(2).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+		assert_eq!(err.to_string(), "unexpected end of file at bit 7 while reading 2 bits");
+		let bit_error = BitError::from_io_error(&err).unwrap();
+		assert_eq!(bit_error.position(), 7);
+		assert_eq!(bit_error.count(), 2);
+		assert!(bit_error.context().is_empty());
+		assert_eq!(reader.read_bit().unwrap(), true);
+		assert_eq!(reader.read_bit().unwrap_err().to_string(), "unexpected end of file at bit 8 while reading 1 bit");
+	}
+
+	#[test]
+	fn context() {
+		let mut reader = BEBitReader::new(&b"\xff"[..]);
+		reader.push_context("header");
+		reader.push_context("flags");
+		reader.read_bits(8).unwrap();
+		let err = reader.read_bit().unwrap_err();
+		assert_eq!(err.to_string(), "unexpected end of file at bit 8 while reading header.flags");
+		assert_eq!(BitError::from_io_error(&err).unwrap().context(), ["header", "flags"]);
+		reader.pop_context();
+		reader.with_context(format!("items[{}]", 3), |reader| reader.read_bit()).unwrap_err();
+		let err = reader.read_bit().unwrap_err();
+		assert_eq!(err.to_string(), "unexpected end of file at bit 8 while reading header");
+	}
+
+	#[test]
+	fn write_error() {
+		let mut buf = [0; 1];
+		let mut writer = BEBitWriter::new(&mut buf[..]);
+		writer.set_padding(crate::Padding::StopBit);
+		writer.with_context("payload", |writer| writer.write_bits(0xff, 8)).unwrap();
+		writer.write_bits(0x03, 2).unwrap();
+		let err = writer.with_context("trailing_bits", |writer| writer.align_to(16)).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::WriteZero);
+		let bit_error = BitError::from_io_error(&err).unwrap();
+		assert_eq!(bit_error.position(), 15);
+		assert_eq!(bit_error.count(), 1);
+		assert!(err.to_string().ends_with(" at bit 15 while writing trailing_bits"));
+		writer.set_drop_policy(crate::DropPolicy::Discard);
+	}
+
+	#[test]
+	fn conversion() {
+		let err = Error::new(ErrorKind::InvalidData, "plain");
+		assert!(BitError::from_io_error(&err).is_none());
+		let mut reader = BEBitReader::new(&b""[..]);
+		let err = reader.read_bit().unwrap_err();
+		let err = Error::new(ErrorKind::InvalidData, err);
+		assert!(BitError::from_io_error(&err).is_none());
+		let mut reader = BEBitReader::new(&b""[..]);
+		let err = reader.read_bits(9).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidInput);
+		let bit_error = err.into_inner().unwrap().downcast::<BitError>().unwrap();
+		assert_eq!(bit_error.count(), 9);
+		let err: Error = (*bit_error).into();
+		assert_eq!(err.kind(), ErrorKind::InvalidInput);
+		let bit_error = BitError::from_io_error(&err).unwrap();
+		assert_eq!(bit_error.io_error().kind(), ErrorKind::InvalidInput);
+	}
+}
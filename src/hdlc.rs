@@ -0,0 +1,302 @@
+use std::io::Result as Res;
+use std::io::{Error, ErrorKind, Read, Write};
+
+use crate::bit_stuffing::{BitDestuffer, BitStuffer, StuffingRule};
+use crate::endian::BitEndianness;
+use crate::read::BitReader;
+use crate::write::{BitWrite, BitWriter};
+
+/// The HDLC flag sequence delimiting frames.
+pub const HDLC_FLAG: u8 = 0x7e;
+
+/**
+	Reads HDLC/PPP frames from a `BitReader`.
+
+	Frames are delimited by flags (`0x7e`), which are detected at arbitrary bit offsets. Within a frame, a 0-bit is stuffed after every five consecutive 1-bits, which is removed by this reader. Seven or more consecutive 1-bits abort the current frame. Frame check sequences are not verified, they are returned as part of the frame.
+
+	HDLC transmits the least significant bit of each byte first, so this is usually used together with `LEBitReader`.
+
+	# Examples
+
+	```
+	use endio_bit::{HdlcReader, LEBitReader};
+
+	let data = b"\xff\x7e\x42\x7e";
+	let mut reader = HdlcReader::new(LEBitReader::new(&data[..]));
+	assert_eq!(reader.read_frame().unwrap(), b"\x42");
+	```
+*/
+#[derive(Debug)]
+pub struct HdlcReader<E: BitEndianness, R: Read> {
+	inner: BitDestuffer<E, R>,
+	/// Whether a flag has been seen since the start of the stream or the last abort.
+	synced: bool,
+	/// Whether six consecutive 1-bits have been read, so the next bit completes a flag or aborts the frame.
+	six_ones: bool,
+	/// Destuffed bits of the current frame, packed into bytes, including the start of a possible closing flag.
+	frame: Vec<u8>,
+	/// Number of bits in `frame`.
+	bit_len: usize,
+	/// Maximum frame length in bytes.
+	max_frame_len: Option<usize>,
+}
+
+impl<E: BitEndianness, R: Read> HdlcReader<E, R> {
+	/// Creates a new `HdlcReader` reading from `inner`.
+	pub fn new(inner: BitReader<E, R>) -> Self {
+		Self {
+			inner: BitDestuffer::new(inner, StuffingRule::HDLC),
+			synced: false,
+			six_ones: false,
+			frame: vec![],
+			bit_len: 0,
+			max_frame_len: None,
+		}
+	}
+
+	/// Returns the maximum frame length in bytes, or `None` if frames aren't limited, which is the default.
+	pub fn max_frame_len(&self) -> Option<usize> {
+		self.max_frame_len
+	}
+
+	/**
+		Sets the maximum frame length in bytes, to bound the memory used on untrusted input.
+
+		When a frame gets longer, it is discarded and `read_frame` fails with an error of kind `QuotaExceeded`. The next call skips to the next flag.
+	*/
+	pub fn set_max_frame_len(&mut self, max_len: Option<usize>) {
+		self.max_frame_len = max_len;
+	}
+
+	/// Gets a reference to the underlying `BitReader`.
+	pub fn get_ref(&self) -> &BitReader<E, R> {
+		self.inner.get_ref()
+	}
+
+	/**
+		Gets a mutable reference to the underlying `BitReader`.
+
+		Reading from the underlying reader will corrupt the frame currently being read.
+	*/
+	pub fn get_mut(&mut self) -> &mut BitReader<E, R> {
+		self.inner.get_mut()
+	}
+
+	/// Unwraps this `HdlcReader`, returning the underlying `BitReader`.
+	pub fn into_inner(self) -> BitReader<E, R> {
+		self.inner.into_inner()
+	}
+
+	/**
+		Reads the next non-empty frame.
+
+		Any data before the first flag is skipped. Aborted frames are skipped as well.
+
+		If the frame is not a multiple of 8 bits long, an error of kind `InvalidData` is returned. If it is longer than the maximum frame length, an error of kind `QuotaExceeded` is returned. Other errors are passed on from the underlying reader, and the partially read frame is kept, so reading can be resumed after errors like `WouldBlock`.
+	*/
+	pub fn read_frame(&mut self) -> Res<Vec<u8>> {
+		loop {
+			if !self.six_ones {
+				match self.inner.read_bit() {
+					Ok(bit) => {
+						if self.synced {
+							self.push_bit(bit)?;
+						}
+						continue;
+					}
+					// a 1-bit where a 0-bit would have been stuffed
+					Err(ref e) if e.kind() == ErrorKind::InvalidData => self.six_ones = true,
+					Err(e) => return Err(e),
+				}
+			}
+			let bit = self.inner.get_mut().read_bit()?;
+			self.six_ones = false;
+			self.inner.reset();
+			if bit {
+				self.discard();
+				continue;
+			}
+			let was_synced = self.synced;
+			self.synced = true;
+			// remove the flag's opening 0-bit and the 1-bits read as data, unless the 0-bit was shared with the previous flag
+			let mut bit_len = self.bit_len;
+			while bit_len > 0 && self.frame[(bit_len - 1) / 8] & E::nth_bit_mask(((bit_len - 1) % 8) as u8, 8) != 0 {
+				bit_len -= 1;
+			}
+			let bit_len = bit_len.saturating_sub(1);
+			let mut frame = std::mem::take(&mut self.frame);
+			self.bit_len = 0;
+			if !was_synced || bit_len == 0 {
+				continue;
+			}
+			if !bit_len.is_multiple_of(8) {
+				return Err(Error::new(ErrorKind::InvalidData, format!("frame length of {} bits is not a multiple of 8", bit_len)));
+			}
+			frame.truncate(bit_len / 8);
+			return Ok(frame);
+		}
+	}
+
+	/// Discards the current frame and waits for the next flag.
+	fn discard(&mut self) {
+		self.synced = false;
+		self.frame.clear();
+		self.bit_len = 0;
+	}
+
+	fn push_bit(&mut self, bit: bool) -> Res<()> {
+		if let Some(max_len) = self.max_frame_len {
+			// leave room for the start of the closing flag
+			if self.bit_len >= max_len.saturating_mul(8).saturating_add(6) {
+				self.discard();
+				return Err(Error::new(ErrorKind::QuotaExceeded, format!("frame longer than {} bytes", max_len)));
+			}
+		}
+		let offset = (self.bit_len % 8) as u8;
+		if offset == 0 {
+			self.frame.push(0);
+		}
+		if bit {
+			*self.frame.last_mut().unwrap() |= E::nth_bit_mask(offset, 8);
+		}
+		self.bit_len += 1;
+		Ok(())
+	}
+}
+
+/**
+	Writes HDLC/PPP frames to a `BitWriter`.
+
+	Each frame is written with an opening and a closing flag (`0x7e`), and with a 0-bit stuffed after every five consecutive 1-bits within the frame. Frame check sequences are not computed, they need to be included in the frame data.
+
+	HDLC transmits the least significant bit of each byte first, so this is usually used together with `LEBitWriter`.
+
+	# Examples
+
+	```
+	use endio_bit::{HdlcWriter, LEBitWriter};
+
+	let mut writer = HdlcWriter::new(LEBitWriter::new(vec![]));
+	writer.write_frame(b"\x42").unwrap();
+	let vec = writer.into_inner().into_inner().unwrap();
+	assert_eq!(vec, b"\x7e\x42\x7e");
+	```
+*/
+#[derive(Debug)]
+pub struct HdlcWriter<E: BitEndianness, W: Write> {
+	inner: BitStuffer<E, W>,
+}
+
+impl<E: BitEndianness, W: Write> HdlcWriter<E, W> {
+	/// Creates a new `HdlcWriter` writing to `inner`.
+	pub fn new(inner: BitWriter<E, W>) -> Self {
+		Self {
+			inner: BitStuffer::new(inner, StuffingRule::HDLC),
+		}
+	}
+
+	/// Gets a reference to the underlying `BitWriter`.
+	pub fn get_ref(&self) -> &BitWriter<E, W> {
+		self.inner.get_ref()
+	}
+
+	/// Gets a mutable reference to the underlying `BitWriter`.
+	pub fn get_mut(&mut self) -> &mut BitWriter<E, W> {
+		self.inner.get_mut()
+	}
+
+	/// Unwraps this `HdlcWriter`, returning the underlying `BitWriter`.
+	pub fn into_inner(self) -> BitWriter<E, W> {
+		self.inner.into_inner()
+	}
+
+	/// Writes a frame, enclosed in flags.
+	pub fn write_frame(&mut self, data: &[u8]) -> Res<()> {
+		self.inner.get_mut().write_bits(HDLC_FLAG, 8)?;
+		for &byte in data {
+			self.inner.write_bits(byte, 8)?;
+		}
+		self.inner.reset()?;
+		self.inner.get_mut().write_bits(HDLC_FLAG, 8)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::ErrorKind;
+	use crate::{HdlcReader, HdlcWriter, LEBitReader, LEBitWriter};
+	use crate::test_util::{retry, ErrorReader};
+
+	#[test]
+	fn write_stuffed() {
+		let mut writer = HdlcWriter::new(LEBitWriter::new(vec![]));
+		writer.write_frame(b"\xff").unwrap();
+		assert_eq!(writer.into_inner().into_inner().unwrap(), b"\x7e\xdf\xfd\x00");
+	}
+
+	#[test]
+	fn read_stuffed() {
+		let mut reader = HdlcReader::new(LEBitReader::new(&b"\x7e\xdf\xfd\x00"[..]));
+		assert_eq!(reader.read_frame().unwrap(), b"\xff");
+	}
+
+	#[test]
+	fn roundtrip() {
+		let frames: [&[u8]; 3] = [b"\x7e\x7e", b"\x00\xff\x1f\xf8", b"\xaa"];
+		let mut writer = HdlcWriter::new(LEBitWriter::new(vec![]));
+		writer.get_mut().write_bits(0x05, 3).unwrap();
+		for frame in &frames {
+			writer.write_frame(frame).unwrap();
+		}
+		let vec = writer.into_inner().into_inner().unwrap();
+		let mut reader = HdlcReader::new(LEBitReader::new(&vec[..]));
+		for frame in &frames {
+			assert_eq!(&reader.read_frame().unwrap()[..], *frame);
+		}
+		assert_eq!(reader.read_frame().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+	}
+
+	#[test]
+	fn read_would_block() {
+		let frames: [&[u8]; 3] = [b"\xff", b"\x3f\x7e", b"\x1f\xf8"];
+		let mut writer = HdlcWriter::new(LEBitWriter::new(vec![]));
+		for frame in &frames {
+			writer.write_frame(frame).unwrap();
+		}
+		let vec = writer.into_inner().into_inner().unwrap();
+		let mut reader = HdlcReader::new(LEBitReader::new(ErrorReader::new(&vec, ErrorKind::WouldBlock)));
+		for frame in &frames {
+			assert_eq!(&retry(|| reader.read_frame())[..], *frame);
+		}
+	}
+
+	#[test]
+	fn shared_zero() {
+		// three flags sharing their 0-bits, followed by a frame
+		let mut reader = HdlcReader::new(LEBitReader::new(&b"\x7e\xbf\x5f\x95\x1f"[..]));
+		assert_eq!(reader.read_frame().unwrap(), b"\x55");
+	}
+
+	#[test]
+	fn abort() {
+		let mut reader = HdlcReader::new(LEBitReader::new(&b"\x7e\x42\xff\x7e\x24\x7e"[..]));
+		assert_eq!(reader.read_frame().unwrap(), b"\x24");
+	}
+
+	#[test]
+	fn unaligned_length() {
+		let mut reader = HdlcReader::new(LEBitReader::new(&b"\x7e\x01\x7e"[..]));
+		let mut reader_shifted = HdlcReader::new(LEBitReader::new(&b"\x7e\x03\xfc\x00"[..]));
+		assert_eq!(reader.read_frame().unwrap(), b"\x01");
+		assert_eq!(reader_shifted.read_frame().unwrap_err().kind(), ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn max_frame_len() {
+		let mut reader = HdlcReader::new(LEBitReader::new(&b"\x7e\x01\x02\x03\x7e\x04\x05\x7e\x06\x7e"[..]));
+		reader.set_max_frame_len(Some(2));
+		assert_eq!(reader.read_frame().unwrap_err().kind(), ErrorKind::QuotaExceeded);
+		assert_eq!(reader.read_frame().unwrap(), b"\x04\x05");
+		assert_eq!(reader.read_frame().unwrap(), b"\x06");
+	}
+}
@@ -0,0 +1,506 @@
+use std::io::Result as Res;
+use std::io::{Error, ErrorKind, Read, Write};
+
+use crate::endian::BitEndianness;
+use crate::read::{BitRead, BitReader};
+use crate::write::{BitWrite, BitWriter};
+
+/**
+	A linear feedback shift register in Fibonacci configuration.
+
+	The register is `degree` bits long. For a generator polynomial x<sup>n</sup> + ... + x<sup>k</sup> + ... + 1, `taps` has bit k-1 set for every term x<sup>k</sup> except the constant term, and `degree` is n. The feedback bit is the parity of the tapped register bits. It is shifted into the least significant bit of the register and is also the output bit.
+
+	# Examples
+
+	```
+	use endio_bit::Lfsr;
+
+	let mut lfsr = Lfsr::prbs7();
+	let seed = lfsr.state();
+	for _ in 0..127 {
+		lfsr.next_bit();
+	}
+	assert_eq!(lfsr.state(), seed);
+	```
+*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lfsr {
+	degree: u8,
+	taps: u64,
+	state: u64,
+}
+
+impl Lfsr {
+	/**
+		Creates a new `Lfsr` with the specified degree, taps and initial state.
+
+		Bits of `taps` and `seed` beyond the degree are ignored.
+
+		# Panics
+
+		Panics if the degree is 0 or greater than 64. Use `try_new` for parameters that aren't known to be valid.
+	*/
+	pub fn new(degree: u8, taps: u64, seed: u64) -> Self {
+		Self::try_new(degree, taps, seed).unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	/// Creates a new `Lfsr` like `new`, but returns an error of kind `InvalidInput` instead of panicking if the degree is 0 or greater than 64.
+	pub fn try_new(degree: u8, taps: u64, seed: u64) -> Res<Self> {
+		if !(1..=64).contains(&degree) {
+			return Err(Error::new(ErrorKind::InvalidInput, "LFSR degree must be between 1 and 64"));
+		}
+		let mask = u64::MAX >> (64 - degree);
+		Ok(Self {
+			degree,
+			taps: taps & mask,
+			state: seed & mask,
+		})
+	}
+
+	/// PRBS7 generator with polynomial x<sup>7</sup> + x<sup>6</sup> + 1, seeded with all ones.
+	pub fn prbs7() -> Self {
+		Self::new(7, 1 << 6 | 1 << 5, u64::MAX)
+	}
+
+	/// PRBS15 generator with polynomial x<sup>15</sup> + x<sup>14</sup> + 1, seeded with all ones.
+	pub fn prbs15() -> Self {
+		Self::new(15, 1 << 14 | 1 << 13, u64::MAX)
+	}
+
+	/// PRBS23 generator with polynomial x<sup>23</sup> + x<sup>18</sup> + 1, seeded with all ones.
+	pub fn prbs23() -> Self {
+		Self::new(23, 1 << 22 | 1 << 17, u64::MAX)
+	}
+
+	/// PRBS31 generator with polynomial x<sup>31</sup> + x<sup>28</sup> + 1, seeded with all ones.
+	pub fn prbs31() -> Self {
+		Self::new(31, 1 << 30 | 1 << 27, u64::MAX)
+	}
+
+	/// Returns the degree of the register.
+	pub fn degree(&self) -> u8 {
+		self.degree
+	}
+
+	/// Returns the current state of the register.
+	pub fn state(&self) -> u64 {
+		self.state
+	}
+
+	/// Sets the state of the register, ignoring bits beyond the degree.
+	pub fn set_state(&mut self, state: u64) {
+		self.state = state & (u64::MAX >> (64 - self.degree));
+	}
+
+	/// Returns the feedback bit for the current state.
+	fn feedback(&self) -> bool {
+		(self.state & self.taps).count_ones() % 2 == 1
+	}
+
+	/// Shifts a bit into the register.
+	fn shift_in(&mut self, bit: bool) {
+		self.set_state(self.state << 1 | bit as u64);
+	}
+
+	/// Advances the register by one step, returning the output bit.
+	pub fn next_bit(&mut self) -> bool {
+		let bit = self.feedback();
+		self.shift_in(bit);
+		bit
+	}
+
+	/// Writes the next `count` output bits to a `BitWriter`.
+	pub fn write_to<E: BitEndianness, W: Write>(&mut self, writer: &mut BitWriter<E, W>, count: u64) -> Res<()> {
+		for _ in 0..count {
+			let bit = self.feedback();
+			writer.write_bit(bit)?;
+			self.shift_in(bit);
+		}
+		Ok(())
+	}
+}
+
+/// Specifies how a scrambler combines the data with its LFSR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScramblerMode {
+	/// The data is XORed with the output of a free-running LFSR, which needs to be synchronized with the receiver. Used by DVB, 802.11 and the SONET frame scrambler.
+	Additive,
+	/// The data is XORed with the feedback of an LFSR that is fed with the scrambled bits, so the descrambler synchronizes itself after `degree` bits. Used by the SONET/SDH payload scrambler.
+	SelfSynchronizing,
+}
+
+/**
+	Scrambles bits written to a `BitWriter`.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitWriter, BitWrite, Lfsr, Scrambler, ScramblerMode};
+
+	let lfsr = Lfsr::new(7, 1 << 6 | 1 << 3, 0x7f);
+	let mut writer = Scrambler::new(BEBitWriter::new(vec![]), lfsr, ScramblerMode::Additive);
+	writer.write_bits(0x00, 8).unwrap();
+	let vec = writer.into_inner().into_inner().unwrap();
+	assert_eq!(vec, b"\x0e");
+	```
+*/
+#[derive(Debug)]
+pub struct Scrambler<E: BitEndianness, W: Write> {
+	inner: BitWriter<E, W>,
+	lfsr: Lfsr,
+	mode: ScramblerMode,
+}
+
+impl<E: BitEndianness, W: Write> Scrambler<E, W> {
+	/// Creates a new `Scrambler` writing to `inner`, scrambling with the specified LFSR and mode.
+	pub fn new(inner: BitWriter<E, W>, lfsr: Lfsr, mode: ScramblerMode) -> Self {
+		Self { inner, lfsr, mode }
+	}
+
+	/// Gets a reference to the LFSR.
+	pub fn lfsr(&self) -> &Lfsr {
+		&self.lfsr
+	}
+
+	/// Gets a mutable reference to the LFSR, e.g. to reseed it at the start of a frame.
+	pub fn lfsr_mut(&mut self) -> &mut Lfsr {
+		&mut self.lfsr
+	}
+
+	/// Gets a reference to the underlying `BitWriter`.
+	pub fn get_ref(&self) -> &BitWriter<E, W> {
+		&self.inner
+	}
+
+	/**
+		Gets a mutable reference to the underlying `BitWriter`.
+
+		Bits written directly to the underlying writer are not scrambled.
+	*/
+	pub fn get_mut(&mut self) -> &mut BitWriter<E, W> {
+		&mut self.inner
+	}
+
+	/// Unwraps this `Scrambler`, returning the underlying `BitWriter`.
+	pub fn into_inner(self) -> BitWriter<E, W> {
+		self.inner
+	}
+
+	/// Writes a single bit, writing 1 for true, 0 for false.
+	pub fn write_bit(&mut self, bit: bool) -> Res<()> {
+		let feedback = self.lfsr.feedback();
+		let scrambled = bit ^ feedback;
+		self.inner.write_bit(scrambled)?;
+		self.lfsr.shift_in(match self.mode {
+			ScramblerMode::Additive => feedback,
+			ScramblerMode::SelfSynchronizing => scrambled,
+		});
+		Ok(())
+	}
+}
+
+impl<E: BitEndianness, W: Write> BitWrite for Scrambler<E, W> {
+	fn write_bit(&mut self, bit: bool) -> Res<()> {
+		Scrambler::write_bit(self, bit)
+	}
+
+	fn msb_first(&self) -> bool {
+		E::msb_first()
+	}
+}
+
+/**
+	Descrambles bits read from a `BitReader`.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitReader, BitRead, Descrambler, Lfsr, ScramblerMode};
+
+	let lfsr = Lfsr::new(7, 1 << 6 | 1 << 3, 0x7f);
+	let mut reader = Descrambler::new(BEBitReader::new(&b"\x0e"[..]), lfsr, ScramblerMode::Additive);
+	assert_eq!(reader.read_bits(8).unwrap(), 0x00);
+	```
+*/
+#[derive(Debug)]
+pub struct Descrambler<E: BitEndianness, R: Read> {
+	inner: BitReader<E, R>,
+	lfsr: Lfsr,
+	mode: ScramblerMode,
+}
+
+impl<E: BitEndianness, R: Read> Descrambler<E, R> {
+	/// Creates a new `Descrambler` reading from `inner`, descrambling with the specified LFSR and mode.
+	pub fn new(inner: BitReader<E, R>, lfsr: Lfsr, mode: ScramblerMode) -> Self {
+		Self { inner, lfsr, mode }
+	}
+
+	/// Gets a reference to the LFSR.
+	pub fn lfsr(&self) -> &Lfsr {
+		&self.lfsr
+	}
+
+	/// Gets a mutable reference to the LFSR, e.g. to reseed it at the start of a frame.
+	pub fn lfsr_mut(&mut self) -> &mut Lfsr {
+		&mut self.lfsr
+	}
+
+	/// Gets a reference to the underlying `BitReader`.
+	pub fn get_ref(&self) -> &BitReader<E, R> {
+		&self.inner
+	}
+
+	/**
+		Gets a mutable reference to the underlying `BitReader`.
+
+		Bits read directly from the underlying reader are not descrambled.
+	*/
+	pub fn get_mut(&mut self) -> &mut BitReader<E, R> {
+		&mut self.inner
+	}
+
+	/// Unwraps this `Descrambler`, returning the underlying `BitReader`.
+	pub fn into_inner(self) -> BitReader<E, R> {
+		self.inner
+	}
+
+	/// Reads a single bit, returning true for 1, false for 0.
+	pub fn read_bit(&mut self) -> Res<bool> {
+		let scrambled = self.inner.read_bit()?;
+		let feedback = self.lfsr.feedback();
+		self.lfsr.shift_in(match self.mode {
+			ScramblerMode::Additive => feedback,
+			ScramblerMode::SelfSynchronizing => scrambled,
+		});
+		Ok(scrambled ^ feedback)
+	}
+}
+
+impl<E: BitEndianness, R: Read> BitRead for Descrambler<E, R> {
+	fn read_bit(&mut self) -> Res<bool> {
+		Descrambler::read_bit(self)
+	}
+
+	fn msb_first(&self) -> bool {
+		E::msb_first()
+	}
+}
+
+/**
+	Checks received bits against a pseudo-random bit sequence, counting bit errors.
+
+	The checker synchronizes itself by loading received bits into its LFSR. Once `degree` further bits match the predicted sequence (excluding the all-zero sequence), it is synchronized, and the LFSR runs freely, so that every bit error is counted exactly once. If too many errors occur within a window of bits (by default more than 16 within 64 bits), synchronization is considered lost and the checker synchronizes again.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitReader, BEBitWriter, Lfsr, PrbsChecker};
+
+	let mut writer = BEBitWriter::new(vec![]);
+	Lfsr::prbs15().write_to(&mut writer, 800).unwrap();
+	let mut vec = writer.into_inner().unwrap();
+	vec[50] ^= 0x10;
+
+	let mut checker = PrbsChecker::new(Lfsr::prbs15());
+	checker.check_from(&mut BEBitReader::new(&vec[..]), 800).unwrap();
+	assert!(checker.is_synced());
+	assert_eq!(checker.bit_errors(), 1);
+	```
+*/
+#[derive(Clone, Debug)]
+pub struct PrbsChecker {
+	lfsr: Lfsr,
+	/// Number of consecutive bits loaded or matched while not synchronized.
+	sync_count: u8,
+	synced: bool,
+	bits_checked: u64,
+	bit_errors: u64,
+	sync_losses: u64,
+	window_bits: u32,
+	window_errors: u32,
+	/// Maximum number of errors within a window before synchronization is lost.
+	max_window_errors: u32,
+	window_size: u32,
+}
+
+impl PrbsChecker {
+	/// Creates a new `PrbsChecker` for the sequence generated by `lfsr`. The state of `lfsr` is irrelevant.
+	pub fn new(lfsr: Lfsr) -> Self {
+		Self {
+			lfsr,
+			sync_count: 0,
+			synced: false,
+			bits_checked: 0,
+			bit_errors: 0,
+			sync_losses: 0,
+			window_bits: 0,
+			window_errors: 0,
+			max_window_errors: 16,
+			window_size: 64,
+		}
+	}
+
+	/// Sets the criterion for loss of synchronization: more than `max_errors` bit errors within `window_size` bits.
+	pub fn set_sync_loss_threshold(&mut self, max_errors: u32, window_size: u32) {
+		self.max_window_errors = max_errors;
+		self.window_size = window_size;
+	}
+
+	/// Returns whether the checker is synchronized to the sequence.
+	pub fn is_synced(&self) -> bool {
+		self.synced
+	}
+
+	/// Returns the number of bits checked while synchronized.
+	pub fn bits_checked(&self) -> u64 {
+		self.bits_checked
+	}
+
+	/// Returns the number of bit errors detected while synchronized.
+	pub fn bit_errors(&self) -> u64 {
+		self.bit_errors
+	}
+
+	/// Returns how often synchronization has been lost.
+	pub fn sync_losses(&self) -> u64 {
+		self.sync_losses
+	}
+
+	/// Checks a single received bit.
+	pub fn check_bit(&mut self, bit: bool) {
+		if !self.synced {
+			let matches = self.lfsr.feedback() == bit;
+			self.lfsr.shift_in(bit);
+			if self.sync_count < self.lfsr.degree() {
+				self.sync_count += 1;
+			} else if matches && self.lfsr.state() != 0 {
+				self.sync_count += 1;
+				if self.sync_count == 2 * self.lfsr.degree() {
+					self.synced = true;
+					self.window_bits = 0;
+					self.window_errors = 0;
+				}
+			} else {
+				self.sync_count = self.lfsr.degree();
+			}
+			return;
+		}
+		self.bits_checked += 1;
+		self.window_bits += 1;
+		if self.lfsr.next_bit() != bit {
+			self.bit_errors += 1;
+			self.window_errors += 1;
+			if self.window_errors > self.max_window_errors {
+				self.synced = false;
+				self.sync_count = 0;
+				self.sync_losses += 1;
+				return;
+			}
+		}
+		if self.window_bits >= self.window_size {
+			self.window_bits = 0;
+			self.window_errors = 0;
+		}
+	}
+
+	/// Reads `count` bits from a `BitReader` and checks them.
+	pub fn check_from<E: BitEndianness, R: Read>(&mut self, reader: &mut BitReader<E, R>, count: u64) -> Res<()> {
+		for _ in 0..count {
+			self.check_bit(reader.read_bit()?);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::ErrorKind;
+	use crate::{BEBitReader, BEBitWriter, BitRead, BitWrite, LEBitReader, LEBitWriter, Descrambler, Lfsr, PrbsChecker, Scrambler, ScramblerMode};
+
+	fn period(mut lfsr: Lfsr) -> u64 {
+		let seed = lfsr.state();
+		let mut period = 0;
+		loop {
+			lfsr.next_bit();
+			period += 1;
+			if lfsr.state() == seed {
+				return period;
+			}
+		}
+	}
+
+	#[test]
+	fn try_new() {
+		assert_eq!(Lfsr::try_new(0, 1, 1).unwrap_err().kind(), ErrorKind::InvalidInput);
+		assert_eq!(Lfsr::try_new(65, 1, 1).unwrap_err().kind(), ErrorKind::InvalidInput);
+		assert_eq!(Lfsr::try_new(7, 1 << 6 | 1 << 5, u64::MAX).unwrap(), Lfsr::prbs7());
+	}
+
+	#[test]
+	fn prbs_period() {
+		assert_eq!(period(Lfsr::prbs7()), 127);
+		assert_eq!(period(Lfsr::prbs15()), 32767);
+	}
+
+	#[test]
+	fn prbs7_sequence() {
+		let mut writer = BEBitWriter::new(vec![]);
+		Lfsr::prbs7().write_to(&mut writer, 16).unwrap();
+		assert_eq!(writer.into_inner().unwrap(), b"\x02\x0c");
+	}
+
+	#[test]
+	fn scramble_roundtrip() {
+		for &mode in &[ScramblerMode::Additive, ScramblerMode::SelfSynchronizing] {
+			let lfsr = Lfsr::new(7, 1 << 6 | 1 << 3, 0x5d);
+			let mut writer = Scrambler::new(LEBitWriter::new(vec![]), lfsr.clone(), mode);
+			for &byte in b"Test data" {
+				writer.write_bits(byte, 8).unwrap();
+			}
+			let vec = writer.into_inner().into_inner().unwrap();
+			assert_ne!(vec, b"Test data");
+			let mut reader = Descrambler::new(LEBitReader::new(&vec[..]), lfsr, mode);
+			for &byte in b"Test data" {
+				assert_eq!(reader.read_bits(8).unwrap(), byte);
+			}
+		}
+	}
+
+	#[test]
+	fn self_synchronizing() {
+		let mut writer = Scrambler::new(BEBitWriter::new(vec![]), Lfsr::new(7, 1 << 6 | 1 << 3, 0x12), ScramblerMode::SelfSynchronizing);
+		for &byte in b"Test data" {
+			writer.write_bits(byte, 8).unwrap();
+		}
+		let vec = writer.into_inner().into_inner().unwrap();
+		let mut reader = Descrambler::new(BEBitReader::new(&vec[..]), Lfsr::new(7, 1 << 6 | 1 << 3, 0x00), ScramblerMode::SelfSynchronizing);
+		reader.read_bits(8).unwrap();
+		for &byte in b"est data" {
+			assert_eq!(reader.read_bits(8).unwrap(), byte);
+		}
+	}
+
+	#[test]
+	fn checker_sync_loss() {
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.write_bits(0xa5, 8).unwrap();
+		let mut lfsr = Lfsr::prbs7();
+		lfsr.write_to(&mut writer, 200).unwrap();
+		for _ in 0..8 {
+			writer.write_bits(0x00, 8).unwrap();
+		}
+		lfsr.write_to(&mut writer, 96).unwrap();
+		let vec = writer.into_inner().unwrap();
+
+		let mut checker = PrbsChecker::new(Lfsr::prbs7());
+		let mut reader = BEBitReader::new(&vec[..]);
+		checker.check_from(&mut reader, 208).unwrap();
+		assert!(checker.is_synced());
+		assert_eq!(checker.bit_errors(), 0);
+		checker.check_from(&mut reader, 64).unwrap();
+		assert!(!checker.is_synced());
+		assert_eq!(checker.sync_losses(), 1);
+		checker.check_from(&mut reader, 96).unwrap();
+		assert!(checker.is_synced());
+	}
+}
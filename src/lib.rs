@@ -5,7 +5,7 @@
 
 	The wrappers are modeled after [`std::io::BufReader`] and [`std::io::BufWriter`], so the semantics and interface should be familiar and robust.
 
-	This crate is a minimal low-level abstraction focusing on bit-level I/O. I recommend using this crate together with [`endio`] if you also need byte-level I/O or (de-)serialization support. However, this crate is completely independent from [`endio`], and can be used standalone if you're only looking for `std::io` with bit support.
+	This crate focuses on bit-level I/O. Besides the wrappers, it provides the building blocks of bit-oriented serial link protocols, which work on the same bit streams. I recommend using this crate together with [`endio`] if you also need byte-level I/O or (de-)serialization support. However, this crate is completely independent from [`endio`], and can be used standalone if you're only looking for `std::io` with bit support.
 
 	### Goals of this crate
 
@@ -13,12 +13,22 @@
 	- Reading and writing of bits that aren't a multiple of 8.
 	- Reading and writing even if the underlying object is bitshifted.
 	- Support for [bit endianness](https://en.wikipedia.org/wiki/Bit_numbering#Most-_vs_least-significant_bit_first) conversion/distinction.
+	- Bit and byte stuffing, HDLC/PPP and CAN 2.0 framing, CRCs over arbitrary bit counts, LFSR scrambling and PRBS checking, and NRZI and Manchester line coding.
+	- Parsing untrusted input and non-blocking I/O, with errors stating the bit position, limits on the data consumed, and retryable operations.
 
 	### Non-goals of this crate
 
 	- Data type (de-)serialization. If you need this, use [`endio`] in combination with this crate.
 	- [Byte endianness](https://en.wikipedia.org/wiki/Endianness) conversion/distinction. If you need this, use [`endio`] in combination with this crate.
 
+	### Panics
+
+	Invalid arguments that may be derived from input data, like bit counts greater than 8, alignment boundaries of 0 or skips and seeks past the maximum position, are reported as errors of kind `InvalidInput`. The remaining panics are:
+
+	- `BitReader::get_mut` and `BitWriter::get_mut` panic if the stream isn't aligned. Use `try_get_mut` instead to get an `Option`.
+	- A `BitWriter` with `DropPolicy::Panic` panics when dropped without being aligned to its final alignment boundary.
+	- `Crc::new`, `Lfsr::new` and `CanFrame::new` panic on invalid parameters, as documented on each. Use their `try_new` counterparts to get an error of kind `InvalidInput` instead.
+
 	### Comparison with other crates
 
 	Bit-level I/O is a common problem, and there are numerous crates on crates.io attempting to provide solutions. However, I haven't been able to find one that is completely satisfactory. Here's a list of related crates and how they differ from this one:
@@ -45,9 +55,35 @@
 	[`Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
 	[`endio`]: https://crates.io/crates/endio
 */
+#![allow(clippy::tabs_in_doc_comments)]
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
+
+mod bit_stuffing;
+mod byte_stuffing;
+mod can;
+mod counting;
+mod crc;
 mod endian;
+mod error;
+mod hdlc;
+mod lfsr;
+mod line_coding;
 mod read;
+mod sync;
+#[cfg(test)]
+mod test_util;
 mod write;
 
+pub use self::bit_stuffing::*;
+pub use self::byte_stuffing::*;
+pub use self::can::*;
+pub use self::counting::*;
+pub use self::crc::*;
+pub use self::endian::{BitEndianness, BigEndian, LittleEndian, BE, LE};
+pub use self::error::*;
+pub use self::hdlc::*;
+pub use self::lfsr::*;
+pub use self::line_coding::*;
 pub use self::read::*;
+pub use self::sync::*;
 pub use self::write::*;
@@ -0,0 +1,304 @@
+use std::io::Result as Res;
+use std::io::{Error, ErrorKind, Read, Write};
+
+use crate::endian::BitEndianness;
+use crate::read::{BitRead, BitReader};
+use crate::write::{BitWrite, BitWriter};
+
+/**
+	Specifies how data bits are represented as line symbols.
+
+	Line levels are represented as bits, with `true` for the high level and `false` for the low level. Manchester codes use two half-bit symbols per data bit.
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineCode {
+	/// Non-return-to-zero inverted: the line level toggles for every data bit of value `toggle_on`, and stays the same otherwise. USB uses `toggle_on: false`.
+	Nrzi { toggle_on: bool },
+	/// Manchester code as used by IEEE 802.3: 0 is a high-to-low transition, 1 is a low-to-high transition.
+	ManchesterIeee,
+	/// Manchester code as originally defined by G. E. Thomas: 0 is a low-to-high transition, 1 is a high-to-low transition.
+	ManchesterThomas,
+	/// Differential Manchester code: every bit has a transition in the middle, 0 additionally has a transition at the start of the bit, 1 doesn't.
+	DifferentialManchester,
+}
+
+/**
+	Decodes line symbols read from a `BitReader` into data bits.
+
+	For Manchester codes, a data bit without a transition in its middle is a phase error. When a phase error is encountered, the error is counted, the decoder resynchronizes by shifting its phase by half a bit, and the read fails with an error of kind `InvalidData`. Reading can continue after the error.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitReader, BitRead, LineCode, LineDecoder};
+
+	let mut reader = LineDecoder::new(BEBitReader::new(&b"\x5a"[..]), LineCode::ManchesterIeee);
+	assert_eq!(reader.read_bits(4).unwrap(), 0x0c);
+	```
+*/
+#[derive(Debug)]
+pub struct LineDecoder<E: BitEndianness, R: Read> {
+	inner: BitReader<E, R>,
+	code: LineCode,
+	/// Line level at the end of the last symbol.
+	level: bool,
+	/// Half-bit symbol that has been read, but not decoded yet.
+	pending: Option<bool>,
+	/// Number of phase errors encountered.
+	phase_errors: u64,
+}
+
+impl<E: BitEndianness, R: Read> LineDecoder<E, R> {
+	/// Creates a new `LineDecoder` reading symbols of the specified line code from `inner`, with an initial line level of low.
+	pub fn new(inner: BitReader<E, R>, code: LineCode) -> Self {
+		Self {
+			inner,
+			code,
+			level: false,
+			pending: None,
+			phase_errors: 0,
+		}
+	}
+
+	/// Sets the line level before the next symbol, which is relevant for NRZI and differential Manchester codes.
+	pub fn set_level(&mut self, level: bool) {
+		self.level = level;
+	}
+
+	/// Returns the number of phase errors encountered, each of which caused a resynchronization.
+	pub fn phase_errors(&self) -> u64 {
+		self.phase_errors
+	}
+
+	/// Gets a reference to the underlying `BitReader`.
+	pub fn get_ref(&self) -> &BitReader<E, R> {
+		&self.inner
+	}
+
+	/**
+		Gets a mutable reference to the underlying `BitReader`.
+
+		Reading from the underlying reader will desynchronize the decoder.
+	*/
+	pub fn get_mut(&mut self) -> &mut BitReader<E, R> {
+		&mut self.inner
+	}
+
+	/// Unwraps this `LineDecoder`, returning the underlying `BitReader`.
+	pub fn into_inner(self) -> BitReader<E, R> {
+		self.inner
+	}
+
+	/// Reads a single data bit, returning true for 1, false for 0.
+	pub fn read_bit(&mut self) -> Res<bool> {
+		if let LineCode::Nrzi { toggle_on } = self.code {
+			let symbol = self.inner.read_bit()?;
+			let transition = symbol != self.level;
+			self.level = symbol;
+			return Ok(transition == toggle_on);
+		}
+		let first = match self.pending.take() {
+			Some(symbol) => symbol,
+			None => self.inner.read_bit()?,
+		};
+		let second = match self.inner.read_bit() {
+			Ok(symbol) => symbol,
+			Err(e) => {
+				self.pending = Some(first);
+				return Err(e);
+			}
+		};
+		if first == second {
+			self.phase_errors += 1;
+			self.level = first;
+			self.pending = Some(second);
+			return Err(Error::new(ErrorKind::InvalidData, "Manchester phase error, resynchronized by half a bit"));
+		}
+		let bit = match self.code {
+			LineCode::ManchesterIeee => second,
+			LineCode::ManchesterThomas => first,
+			LineCode::DifferentialManchester => first == self.level,
+			LineCode::Nrzi { .. } => unreachable!(),
+		};
+		self.level = second;
+		Ok(bit)
+	}
+}
+
+impl<E: BitEndianness, R: Read> BitRead for LineDecoder<E, R> {
+	fn read_bit(&mut self) -> Res<bool> {
+		LineDecoder::read_bit(self)
+	}
+
+	fn msb_first(&self) -> bool {
+		E::msb_first()
+	}
+}
+
+/**
+	Encodes data bits into line symbols written to a `BitWriter`.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitWriter, BitWrite, LineCode, LineEncoder};
+
+	let mut writer = LineEncoder::new(BEBitWriter::new(vec![]), LineCode::ManchesterIeee);
+	writer.write_bits(0x0c, 4).unwrap();
+	let vec = writer.into_inner().into_inner().unwrap();
+	assert_eq!(vec, b"\x5a");
+	```
+*/
+#[derive(Debug)]
+pub struct LineEncoder<E: BitEndianness, W: Write> {
+	inner: BitWriter<E, W>,
+	code: LineCode,
+	/// Line level at the end of the last symbol.
+	level: bool,
+}
+
+impl<E: BitEndianness, W: Write> LineEncoder<E, W> {
+	/// Creates a new `LineEncoder` writing symbols of the specified line code to `inner`, with an initial line level of low.
+	pub fn new(inner: BitWriter<E, W>, code: LineCode) -> Self {
+		Self {
+			inner,
+			code,
+			level: false,
+		}
+	}
+
+	/// Sets the line level before the next symbol, which is relevant for NRZI and differential Manchester codes.
+	pub fn set_level(&mut self, level: bool) {
+		self.level = level;
+	}
+
+	/// Gets a reference to the underlying `BitWriter`.
+	pub fn get_ref(&self) -> &BitWriter<E, W> {
+		&self.inner
+	}
+
+	/**
+		Gets a mutable reference to the underlying `BitWriter`.
+
+		Symbols written directly to the underlying writer don't update the line level.
+	*/
+	pub fn get_mut(&mut self) -> &mut BitWriter<E, W> {
+		&mut self.inner
+	}
+
+	/// Unwraps this `LineEncoder`, returning the underlying `BitWriter`.
+	pub fn into_inner(self) -> BitWriter<E, W> {
+		self.inner
+	}
+
+	/// Writes a single data bit, writing 1 for true, 0 for false.
+	pub fn write_bit(&mut self, bit: bool) -> Res<()> {
+		let first = match self.code {
+			LineCode::Nrzi { toggle_on } => {
+				let symbol = self.level ^ (bit == toggle_on);
+				self.inner.write_bit(symbol)?;
+				self.level = symbol;
+				return Ok(());
+			}
+			LineCode::ManchesterIeee => !bit,
+			LineCode::ManchesterThomas => bit,
+			LineCode::DifferentialManchester => self.level == bit,
+		};
+		let symbol = E::nth_bit_mask(if first { 0 } else { 1 }, 2);
+		self.inner.write_bits(symbol, 2)?;
+		self.level = !first;
+		Ok(())
+	}
+}
+
+impl<E: BitEndianness, W: Write> BitWrite for LineEncoder<E, W> {
+	fn write_bit(&mut self, bit: bool) -> Res<()> {
+		LineEncoder::write_bit(self, bit)
+	}
+
+	fn msb_first(&self) -> bool {
+		E::msb_first()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::ErrorKind;
+	use crate::{BEBitReader, BEBitWriter, BitRead, BitWrite, LEBitReader, LEBitWriter, LineCode, LineDecoder, LineEncoder};
+	use crate::test_util::{retry, ErrorWriter};
+
+	fn encode(code: LineCode, bits: u8) -> Vec<u8> {
+		let mut writer = LineEncoder::new(BEBitWriter::new(vec![]), code);
+		writer.write_bits(bits, 8).unwrap();
+		writer.into_inner().into_inner().unwrap()
+	}
+
+	#[test]
+	fn encode_nrzi() {
+		assert_eq!(encode(LineCode::Nrzi { toggle_on: false }, 0xd2), b"\x36");
+		assert_eq!(encode(LineCode::Nrzi { toggle_on: true }, 0xd2), b"\x9c");
+	}
+
+	#[test]
+	fn encode_manchester() {
+		assert_eq!(encode(LineCode::ManchesterIeee, 0xd2), b"\x59\xa6");
+		assert_eq!(encode(LineCode::ManchesterThomas, 0xd2), b"\xa6\x59");
+		assert_eq!(encode(LineCode::DifferentialManchester, 0xd2), b"\x69\x5a");
+	}
+
+	#[test]
+	fn encode_would_block() {
+		let mut writer = LineEncoder::new(BEBitWriter::new(ErrorWriter::new(ErrorKind::WouldBlock)), LineCode::ManchesterIeee);
+		for i in 0..8 {
+			retry(|| writer.write_bit(0xd2 & 0x80 >> i != 0));
+		}
+		assert_eq!(writer.into_inner().into_inner().unwrap().data, b"\x59\xa6");
+	}
+
+	#[test]
+	fn encode_le() {
+		let mut writer = LineEncoder::new(LEBitWriter::new(vec![]), LineCode::ManchesterIeee);
+		writer.write_bits(0x0b, 4).unwrap();
+		assert_eq!(writer.into_inner().into_inner().unwrap(), b"\x9a");
+	}
+
+	#[test]
+	fn roundtrip() {
+		let codes = [LineCode::Nrzi { toggle_on: false }, LineCode::Nrzi { toggle_on: true }, LineCode::ManchesterIeee, LineCode::ManchesterThomas, LineCode::DifferentialManchester];
+		for &code in &codes {
+			for &level in &[false, true] {
+				let mut writer = LineEncoder::new(LEBitWriter::new(vec![]), code);
+				writer.set_level(level);
+				for &byte in b"Test" {
+					writer.write_bits(byte, 8).unwrap();
+				}
+				let vec = writer.into_inner().into_inner().unwrap();
+				let mut reader = LineDecoder::new(LEBitReader::new(&vec[..]), code);
+				reader.set_level(level);
+				for &byte in b"Test" {
+					assert_eq!(reader.read_bits(8).unwrap(), byte);
+				}
+				assert_eq!(reader.phase_errors(), 0);
+			}
+		}
+	}
+
+	#[test]
+	fn phase_error() {
+		// 0xd2 encoded, with the first half-bit symbol missing
+		let mut reader = LineDecoder::new(BEBitReader::new(&b"\xb3\x4c"[..]), LineCode::ManchesterIeee);
+		assert_eq!(reader.read_bit().unwrap(), false);
+		assert_eq!(reader.read_bit().unwrap_err().kind(), ErrorKind::InvalidData);
+		assert_eq!(reader.phase_errors(), 1);
+		assert_eq!(reader.read_bits(6).unwrap(), 0x12);
+		assert_eq!(reader.phase_errors(), 1);
+	}
+
+	#[test]
+	fn read_error_keeps_phase() {
+		let mut reader = LineDecoder::new(BEBitReader::new(&b"\x59"[..]), LineCode::ManchesterIeee);
+		assert_eq!(reader.read_bits(4).unwrap(), 0x0d);
+		assert_eq!(reader.read_bit().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+		assert_eq!(reader.phase_errors(), 0);
+	}
+}
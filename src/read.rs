@@ -1,18 +1,123 @@
+use std::borrow::Cow;
 use std::io::Result as Res;
-use std::io::Read;
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
+use std::ops::{Deref, DerefMut};
 
 use crate::endian::{BitEndianness, BE, LE};
+use crate::error::BitError;
+use crate::write::Padding;
 
 /// Reads most significant bits first.
 pub type BEBitReader<R> = BitReader<BE, R>;
 /// Reads least significant bits first.
 pub type LEBitReader<R> = BitReader<LE, R>;
 
+/**
+	Limits on the data consumed by a `BitReader`, to bound the resources spent on untrusted input.
+
+	When a limit would be exceeded, the operation fails with an error of kind `QuotaExceeded`. Operations that would exceed the limit on the total number of bits fail before consuming any bits. `None` means no limit, which is the default.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitReader, Limits};
+	use std::io::ErrorKind;
+
+	let mut reader = BEBitReader::new(&b"\xff\xff\xff"[..]);
+	reader.set_limits(Limits { max_bits: Some(16), max_unary_len: Some(12), ..Limits::default() });
+	assert_eq!(reader.read_unary(false).unwrap_err().kind(), ErrorKind::QuotaExceeded);
+	assert_eq!(reader.read_bits(3).unwrap(), 0x07);
+	assert_eq!(reader.read_bit().unwrap_err().kind(), ErrorKind::QuotaExceeded);
+	```
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+	/// Maximum number of bits read or skipped in total since the creation of the reader. Bits discarded by `align` aren't limited, since they have already been read from the underlying reader. Bits read again after restoring a checkpoint count again.
+	pub max_bits: Option<u64>,
+	/// Maximum number of bits before the stop bit of a unary code read by `read_unary`.
+	pub max_unary_len: Option<u32>,
+	/// Maximum number of bytes of a varint read by `read_varint`.
+	pub max_varint_len: Option<u32>,
+}
+
+/**
+	A source of bits, implemented by `BitReader` and the bit-level reading adapters of this crate.
+
+	The trait is object safe, so parsers can be written once for any bit source, taking `&mut dyn BitRead` or a generic `impl BitRead`.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitReader, BitRead, LEBitReader};
+	use std::io::Result;
+
+	fn parse_header(reader: &mut dyn BitRead) -> Result<(bool, u8)> {
+		Ok((reader.read_bit()?, reader.read_bits(3)?))
+	}
+
+	assert_eq!(parse_header(&mut BEBitReader::new(&b"\xd0"[..])).unwrap(), (true, 5));
+	assert_eq!(parse_header(&mut LEBitReader::new(&b"\x0b"[..])).unwrap(), (true, 5));
+	```
+*/
+pub trait BitRead {
+	/// Reads a single bit, returning true for 1, false for 0.
+	fn read_bit(&mut self) -> Res<bool>;
+
+	/// Returns whether the most significant bit of a value is read first, i.e. whether the bits are numbered big endian.
+	fn msb_first(&self) -> bool;
+
+	/**
+		Reads 8 bits or less into the lowest `count` bits. If `count` > 8, an error of kind `InvalidInput` is returned.
+
+		The default implementation reads the bits one by one with `read_bit`, in the order given by `msb_first`.
+	*/
+	fn read_bits(&mut self, count: u8) -> Res<u8> {
+		check_bit_count(count)?;
+		let mut res = 0;
+		for i in 0..count {
+			if self.read_bit()? {
+				res |= if self.msb_first() { 1 << (count - 1 - i) } else { 1 << i };
+			}
+		}
+		Ok(res)
+	}
+}
+
+impl<T: BitRead + ?Sized> BitRead for &mut T {
+	fn read_bit(&mut self) -> Res<bool> {
+		(**self).read_bit()
+	}
+
+	fn msb_first(&self) -> bool {
+		(**self).msb_first()
+	}
+
+	fn read_bits(&mut self, count: u8) -> Res<u8> {
+		(**self).read_bits(count)
+	}
+}
+
+impl<T: BitRead + ?Sized> BitRead for Box<T> {
+	fn read_bit(&mut self) -> Res<bool> {
+		(**self).read_bit()
+	}
+
+	fn msb_first(&self) -> bool {
+		(**self).msb_first()
+	}
+
+	fn read_bits(&mut self, count: u8) -> Res<u8> {
+		(**self).read_bits(count)
+	}
+}
+
 /**
 	Adds bit-level reading support to something implementing [`std::io::Read`].
 
 	This is accomplished through an internal buffer for storing partially read bytes. Note that this buffer is for correctness, not performance - if you want to improve performance by buffering, use [`std::io::BufReader`] as the `BitReader`'s data source.
 
+	Errors of kind `Interrupted` returned by the underlying reader are retried internally. Reading bits and bytes fails with any other error without changing the `BitReader`, so it can be retried, e.g. after a `WouldBlock` error from a non-blocking source. Operations made up of several reads, like `read_unary`, `read_varint`, `skip_bits`, the alignment methods and `find_sync`, keep the data read before the error consumed, as documented on each of them. Errors of bit-level operations wrap a `BitError` describing the position in the stream and the context labels at which they occurred.
+
 	To use this reader, you'll have to choose a bit endianness to read in. The bit endianness determines the direction in which bits in a byte will be read. Note that this is distinct from byte endianness, and e.g. a format which is little endian at the byte level is not necessarily little endian at the bit level.
 
 	If you don't already know which bit endianness you need, chances are you need big endian bit numbering. In that case, just `use endio_bit::BEBitReader`. Otherwise `use endio_bit::LEBitReader`.
@@ -20,6 +125,7 @@ pub type LEBitReader<R> = BitReader<LE, R>;
 	[`std::io::Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
 	[`std::io::BufReader`]: https://doc.rust-lang.org/std/io/struct.BufReader.html
 */
+#[derive(Debug)]
 pub struct BitReader<E: BitEndianness, R: Read> {
 	/// Data to read from.
 	inner: R,
@@ -27,6 +133,23 @@ pub struct BitReader<E: BitEndianness, R: Read> {
 	bit_offset: u8,
 	/// Storage for remaining bits after an unaligned read operation.
 	bit_buffer: u8,
+	/// Number of bits read or skipped.
+	bit_count: u64,
+	/// Value of `bit_count` when `set_mark` was last called.
+	mark: u64,
+	/// Labels of the fields being read, for error messages.
+	context: Vec<Cow<'static, str>>,
+	limits: Limits,
+	/// Bit count at the end of the innermost section taken with `take_bits`.
+	section_end: Option<u64>,
+	/// Bytes read from the underlying reader since the oldest active checkpoint, and bytes to be read again after restoring one.
+	replay: Vec<u8>,
+	/// Position of the next byte to read from `replay`, bytes are read from the underlying reader if it's at the end.
+	replay_pos: usize,
+	/// Number of active checkpoints created by `checkpoint`.
+	checkpoints: usize,
+	/// Number of bits returned to by restoring checkpoints, which still count towards the bit budget.
+	restored_bits: u64,
 	phantom: std::marker::PhantomData<E>,
 }
 
@@ -52,6 +175,15 @@ impl<E: BitEndianness, R: Read> BitReader<E, R> {
 			inner,
 			bit_offset: 0,
 			bit_buffer: 0,
+			bit_count: 0,
+			mark: 0,
+			context: vec![],
+			limits: Limits::default(),
+			section_end: None,
+			replay: vec![],
+			replay_pos: 0,
+			checkpoints: 0,
+			restored_bits: 0,
 			phantom: std::marker::PhantomData,
 		}
 	}
@@ -62,12 +194,234 @@ impl<E: BitEndianness, R: Read> BitReader<E, R> {
 		self.bit_offset == 0
 	}
 
+	/**
+		Returns the number of bits of the current partial byte that haven't been read yet.
+
+		Returns 0 if the `BitReader` is aligned.
+	*/
+	pub fn buffered_bits(&self) -> u8 {
+		(8 - self.bit_offset) % 8
+	}
+
+	/// Returns the offset of the next bit to read within the current byte, 0 if the `BitReader` is aligned.
+	pub fn bit_offset(&self) -> u8 {
+		self.bit_offset
+	}
+
+	/**
+		Returns the number of bits read or skipped since the creation of the `BitReader`, including bits read through the `Read` implementation and discarded by `align`.
+
+		Restoring a checkpoint returns to the position at the checkpoint. Bytes read directly from the underlying reader are not taken into account.
+
+		# Examples
+
+		```
+		# use endio_bit::BEBitReader;
+		# use std::io::Read;
+		let mut reader = BEBitReader::new(&b"\x12\x34\x56\x78"[..]);
+		reader.read_bits(3).unwrap();
+		reader.set_mark();
+		reader.read_exact(&mut [0; 2]).unwrap();
+		reader.align();
+		assert_eq!(reader.bit_position(), 24);
+		assert_eq!(reader.bits_since_mark(), 21);
+		assert_eq!(reader.bit_offset(), 0);
+		```
+	*/
+	pub fn bit_position(&self) -> u64 {
+		self.bit_count
+	}
+
+	/// Sets the mark to the current position, for measuring the bits read from it with `bits_since_mark`.
+	pub fn set_mark(&mut self) {
+		self.mark = self.bit_count;
+	}
+
+	/// Returns the number of bits read or skipped since the last call of `set_mark`, or since the creation of the `BitReader` if there was none. Returns 0 if the position is before the mark, e.g. after restoring a checkpoint.
+	pub fn bits_since_mark(&self) -> u64 {
+		self.bit_count.saturating_sub(self.mark)
+	}
+
 	/// Aligns to byte boundary, discarding a partial byte if the `BitReader` was not aligned.
 	pub fn align(&mut self) {
+		self.bit_count += self.buffered_bits() as u64;
 		self.bit_offset = 0;
 		self.bit_buffer = 0;
 	}
 
+	/// Returns the limits on the data consumed by this reader.
+	pub fn limits(&self) -> Limits {
+		self.limits
+	}
+
+	/// Sets the limits on the data consumed by this reader.
+	pub fn set_limits(&mut self, limits: Limits) {
+		self.limits = limits;
+	}
+
+	/// Returns the number of bits counting towards the bit budget, including those read again after restoring checkpoints.
+	fn budget_used(&self) -> u64 {
+		self.bit_count.saturating_add(self.restored_bits)
+	}
+
+	/// Returns an error of kind `UnexpectedEof` if reading `count` more bits would go past the end of the section, or of kind `QuotaExceeded` if it would exceed the bit budget.
+	fn check_budget(&self, count: u64) -> Res<()> {
+		if let Some(end) = self.section_end {
+			if self.bit_count.saturating_add(count) > end {
+				return Err(Error::new(ErrorKind::UnexpectedEof, "unexpected end of section"));
+			}
+		}
+		match self.limits.max_bits {
+			Some(max_bits) if self.budget_used().saturating_add(count) > max_bits => Err(Error::new(ErrorKind::QuotaExceeded, format!("bit budget of {} bits exceeded", max_bits))),
+			_ => Ok(()),
+		}
+	}
+
+	/**
+		Returns a section of this reader limited to the next `n` bits, for handing a length-delimited part of the stream to a parser that must not read past it.
+
+		The section derefs to this reader, and reports the end of the data after exactly `n` bits. When it's dropped, the rest of the section is skipped. Sections can be nested.
+
+		# Examples
+
+		```
+		use endio_bit::BEBitReader;
+		use std::io::ErrorKind;
+
+		let mut reader = BEBitReader::new(&b"\xab\xcd\xef"[..]);
+		{
+			let mut section = reader.take_bits(12);
+			assert_eq!(section.read_bits(8).unwrap(), 0xab);
+			assert_eq!(section.read_bits(5).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+			assert_eq!(section.read_bits(3).unwrap(), 0x06);
+		}
+		assert_eq!(reader.read_bits(4).unwrap(), 0x0d);
+		```
+	*/
+	pub fn take_bits(&mut self, n: u64) -> TakeBits<'_, E, R> {
+		let outer_end = self.section_end;
+		let end = self.bit_count.saturating_add(n);
+		self.section_end = Some(outer_end.map_or(end, |outer_end| std::cmp::min(end, outer_end)));
+		TakeBits { reader: self, outer_end, skip_on_drop: true }
+	}
+
+	/// Pushes a label onto the context stack, describing the field being read in error messages.
+	pub fn push_context(&mut self, label: impl Into<Cow<'static, str>>) {
+		self.context.push(label.into());
+	}
+
+	/// Pops the innermost label from the context stack.
+	pub fn pop_context(&mut self) {
+		self.context.pop();
+	}
+
+	/// Calls `f` with `label` pushed onto the context stack, popping it afterwards even if `f` fails.
+	pub fn with_context<T>(&mut self, label: impl Into<Cow<'static, str>>, f: impl FnOnce(&mut Self) -> Res<T>) -> Res<T> {
+		self.push_context(label);
+		let res = f(self);
+		self.pop_context();
+		res
+	}
+
+	/// Wraps `error` in a `BitError` at the current position.
+	fn error(&self, count: u32, error: Error) -> Error {
+		self.error_at(self.bit_count, count, error)
+	}
+
+	/// Wraps `error` in a `BitError` at `position`, for operations that report the position at which they started.
+	pub(crate) fn error_at(&self, position: u64, count: u32, error: Error) -> Error {
+		BitError::new(position, count, &self.context, false, error).into()
+	}
+
+	/**
+		Aligns to a boundary of `boundary` bits relative to the start of the stream, discarding the bits up to it.
+
+		The stream starts at the creation of the `BitReader`. Bytes read directly from the underlying reader are not taken into account.
+
+		Bits are skipped byte by byte. If an error occurs, the bits skipped so far are consumed, and calling `align_to` again completes the alignment. If `boundary` is 0, an error of kind `InvalidInput` is returned.
+	*/
+	pub fn align_to(&mut self, boundary: u32) -> Res<()> {
+		self.skip_padding(boundary, None)
+	}
+
+	/// Aligns to byte boundary, returning an error if the bits of a partial byte aren't 0. Equivalent to `align_expect(Padding::Zeros)`.
+	pub fn align_expect_zero(&mut self) -> Res<()> {
+		self.align_expect(Padding::Zeros)
+	}
+
+	/**
+		Aligns to byte boundary, returning an error if the bits up to it don't match `padding`.
+
+		With `Padding::StopBit`, a 1-bit followed by 0-bits is expected, as in H.264 RBSP trailing bits. The 1-bit is always expected, so if the reader is aligned, a whole byte of padding is read.
+
+		If the padding is invalid, an error of kind `InvalidData` is returned, describing the bit position and the bits found. The invalid bits are consumed.
+
+		# Examples
+
+		```
+		use endio_bit::{BEBitReader, Padding};
+
+		let mut reader = BEBitReader::new(&b"\xb0\xac"[..]);
+		assert_eq!(reader.read_bits(3).unwrap(), 0x05);
+		reader.align_expect(Padding::StopBit).unwrap();
+		assert_eq!(reader.read_bits(3).unwrap(), 0x05);
+		let err = reader.align_expect(Padding::StopBit).unwrap_err();
+		assert_eq!(err.to_string(), "expected padding 10000, found 01100 at bit 11 while reading 5 bits");
+		```
+	*/
+	pub fn align_expect(&mut self, padding: Padding) -> Res<()> {
+		self.skip_padding(8, Some(padding))
+	}
+
+	/**
+		Aligns to a boundary of `boundary` bits relative to the start of the stream, as with `align_to`, returning an error if the bits up to it don't match `padding`, as with `align_expect`.
+
+		If `boundary` is 0, an error of kind `InvalidInput` is returned.
+	*/
+	pub fn align_to_expect(&mut self, boundary: u32, padding: Padding) -> Res<()> {
+		self.skip_padding(boundary, Some(padding))
+	}
+
+	/// Skips bits up to `boundary`, byte by byte, validating them against `padding` if specified.
+	fn skip_padding(&mut self, boundary: u32, padding: Option<Padding>) -> Res<()> {
+		if boundary == 0 {
+			return Err(self.error(0, Error::new(ErrorKind::InvalidInput, "alignment boundary must not be 0")));
+		}
+		let boundary = boundary as u64;
+		let mut stop_bit = padding == Some(Padding::StopBit);
+		while stop_bit || !self.bit_count.is_multiple_of(boundary) {
+			let start = self.bit_offset;
+			let count = std::cmp::min(8 - start as u64, boundary - self.bit_count % boundary) as u8;
+			self.check_budget(count as u64).map_err(|e| self.error(count as u32, e))?;
+			let byte = if self.is_aligned() { self.read_byte().map_err(|e| self.error(count as u32, e))? } else { self.bit_buffer };
+			let mut found = 0u8;
+			let mut expected = 0u8;
+			if let Some(padding) = padding {
+				for offset in start..start + count {
+					let mask = E::shift_lsb(E::shift_msb(0xff, 7), offset);
+					let bit = match padding {
+						Padding::Zeros => false,
+						Padding::Ones => true,
+						Padding::StopBit => stop_bit && offset == start,
+						Padding::Pattern(pattern) => pattern & mask != 0,
+					};
+					found = found << 1 | (byte & mask != 0) as u8;
+					expected = expected << 1 | bit as u8;
+				}
+			}
+			let position = self.bit_count;
+			self.bit_buffer = byte;
+			self.bit_offset = (start + count) % 8;
+			self.bit_count += count as u64;
+			stop_bit = false;
+			if found != expected {
+				let error = Error::new(ErrorKind::InvalidData, format!("expected padding {:02$b}, found {:02$b}", expected, found, count as usize));
+				return Err(BitError::new(position, count as u32, &self.context, false, error).into());
+			}
+		}
+		Ok(())
+	}
+
 	/**
 		Gets a reference to the underlying reader.
 
@@ -77,9 +431,9 @@ impl<E: BitEndianness, R: Read> BitReader<E, R> {
 		# let mut reader = BEBitReader::new(&b"\x00"[..]);
 		# let inner = reader.get_ref();
 		# let mut buf = [0; 1];
-		# inner.read(&mut buf).unwrap();
+		# inner.read_exact(&mut buf).unwrap();
 		```
-	**/
+	*/
 	pub fn get_ref(&self) -> &R {
 		&self.inner
 	}
@@ -89,7 +443,9 @@ impl<E: BitEndianness, R: Read> BitReader<E, R> {
 
 		Mutable operations on the underlying reader will corrupt this `BitReader` if it is not aligned, so the reference is only returned if the `BitReader` is aligned.
 
-		Panics if the `BitReader` is not aligned.
+		# Panics
+
+		Panics if the `BitReader` is not aligned. Use `try_get_mut` to avoid the panic.
 	*/
 	pub fn get_mut(&mut self) -> &mut R {
 		if !self.is_aligned() {
@@ -98,10 +454,22 @@ impl<E: BitEndianness, R: Read> BitReader<E, R> {
 		&mut self.inner
 	}
 
+	/// Gets a mutable reference to the underlying reader like `get_mut`, but returns `None` instead of panicking if the `BitReader` is not aligned.
+	pub fn try_get_mut(&mut self) -> Option<&mut R> {
+		if !self.is_aligned() {
+			return None;
+		}
+		Some(&mut self.inner)
+	}
+
 	/**
 		Gets a mutable reference to the underlying reader.
 
 		Use with care: Any reading/seeking/etc operation on the underlying reader will corrupt this `BitReader` if it is not aligned.
+
+		# Safety
+
+		The caller must not modify the state of the underlying reader while the `BitReader` is not aligned.
 	*/
 	pub unsafe fn get_mut_unchecked(&mut self) -> &mut R {
 		&mut self.inner
@@ -110,17 +478,48 @@ impl<E: BitEndianness, R: Read> BitReader<E, R> {
 	/**
 		Unwraps this `BitReader`, returning the underlying reader.
 
-		Note that any partially read byte is lost.
+		Note that any partially read byte is lost, as are any bytes to be read again after restoring a checkpoint.
 	*/
 	pub fn into_inner(self) -> R {
 		self.inner
 	}
 
-	fn fill_buffer(&mut self) -> Res<()> {
+	/// Reads from the replay buffer, or from the underlying reader if there's nothing to replay, recording the bytes read while checkpoints are active.
+	fn read_inner(&mut self, buf: &mut [u8]) -> Res<usize> {
+		if self.replay_pos < self.replay.len() {
+			let len = std::cmp::min(buf.len(), self.replay.len() - self.replay_pos);
+			buf[..len].copy_from_slice(&self.replay[self.replay_pos..self.replay_pos + len]);
+			self.replay_pos += len;
+			self.trim_replay();
+			return Ok(len);
+		}
+		let len = self.inner.read(buf)?;
+		if self.checkpoints > 0 {
+			self.replay.extend_from_slice(&buf[..len]);
+			self.replay_pos = self.replay.len();
+		}
+		Ok(len)
+	}
+
+	/// Frees the replay buffer if there are no active checkpoints and nothing left to replay.
+	fn trim_replay(&mut self) {
+		if self.checkpoints == 0 && self.replay_pos == self.replay.len() {
+			self.replay = vec![];
+			self.replay_pos = 0;
+		}
+	}
+
+	/// Reads the next byte, without modifying the state of the `BitReader` apart from the replay buffer.
+	fn read_byte(&mut self) -> Res<u8> {
 		let mut temp = [0; 1];
-		self.inner.read_exact(&mut temp)?;
-		self.bit_buffer = temp[0];
-		Ok(())
+		loop {
+			match self.read_inner(&mut temp) {
+				Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+				Ok(_) => return Ok(temp[0]),
+				Err(e) if e.kind() == ErrorKind::Interrupted => {}
+				Err(e) => return Err(e),
+			}
+		}
 	}
 
 	/**
@@ -141,13 +540,15 @@ impl<E: BitEndianness, R: Read> BitReader<E, R> {
 		let value = reader.read_bit().unwrap();
 		assert_eq!(value, true);
 		```
-	**/
+	*/
 	pub fn read_bit(&mut self) -> Res<bool> {
+		self.check_budget(1).map_err(|e| self.error(1, e))?;
 		if self.is_aligned() {
-			self.fill_buffer()?;
+			self.bit_buffer = self.read_byte().map_err(|e| self.error(1, e))?;
 		}
 		let val = self.bit_buffer & (E::shift_lsb(E::shift_msb(0xff, 7), self.bit_offset)) != 0;
 		self.bit_offset = (self.bit_offset + 1) % 8;
+		self.bit_count += 1;
 		Ok(val)
 	}
 
@@ -156,11 +557,7 @@ impl<E: BitEndianness, R: Read> BitReader<E, R> {
 
 		The lowest `count` bits will be filled by this, the others will be zero.
 
-		Reading more than 8 bits is intentionally not supported to keep the interface simple. Reading more can be accomplished by reading bytes and then reading any leftover bits.
-
-		# Panics
-
-		Panics if `count` > 8.
+		Reading more than 8 bits is intentionally not supported to keep the interface simple. Reading more can be accomplished by reading bytes and then reading any leftover bits. If `count` > 8, an error of kind `InvalidInput` is returned.
 
 		# Examples
 
@@ -179,22 +576,346 @@ impl<E: BitEndianness, R: Read> BitReader<E, R> {
 		```
 	*/
 	pub fn read_bits(&mut self, count: u8) -> Res<u8> {
-		assert!(count <= 8);
+		check_bit_count(count).and_then(|()| self.check_budget(count as u64)).map_err(|e| self.error(count as u32, e))?;
+		if count == 0 {
+			return Ok(0);
+		}
+		let mut bit_buffer = self.bit_buffer;
 		if self.is_aligned() {
-			self.fill_buffer()?;
+			bit_buffer = self.read_byte().map_err(|e| self.error(count as u32, e))?;
 		}
 		let start = self.bit_offset;
 		let end = start + count;
-		let mut res = E::shift_msb(self.bit_buffer, start);
+		let mut res = E::shift_msb(bit_buffer, start);
 		if end > 8 {
-			self.fill_buffer()?;
-			res |= E::shift_lsb(self.bit_buffer, 8 - start);
+			bit_buffer = self.read_byte().map_err(|e| self.error(count as u32, e))?;
+			res |= E::shift_lsb(bit_buffer, 8 - start);
 		}
 		res = E::shift_lsb(res, 8 - count);
 		res = E::align_right(res, count);
+		self.bit_buffer = bit_buffer;
 		self.bit_offset = end % 8;
+		self.bit_count += count as u64;
 		Ok(res)
 	}
+
+	/**
+		Reads a unary code, returning the number of bits before the first bit equal to `stop_bit`. The stop bit is consumed.
+
+		If the code is longer than allowed by `Limits::max_unary_len`, an error of kind `QuotaExceeded` is returned. If an error occurs, the bits read so far are consumed.
+
+		# Examples
+
+		```
+		# use endio_bit::BEBitReader;
+		let mut reader = BEBitReader::new(&b"\x0b"[..]);
+		assert_eq!(reader.read_unary(true).unwrap(), 4);
+		assert_eq!(reader.read_unary(false).unwrap(), 0);
+		```
+	*/
+	pub fn read_unary(&mut self, stop_bit: bool) -> Res<u32> {
+		let max_len = self.limits.max_unary_len.unwrap_or(u32::MAX);
+		let start = self.bit_count;
+		let mut len = 0;
+		while self.read_bit()? != stop_bit {
+			if len == max_len {
+				let error = Error::new(ErrorKind::QuotaExceeded, format!("unary code longer than {} bits", max_len));
+				return Err(BitError::new(start, len.saturating_add(1), &self.context, false, error).into());
+			}
+			len += 1;
+		}
+		Ok(len)
+	}
+
+	/**
+		Reads an unsigned LEB128 varint, as used e.g. by Protocol Buffers, consisting of 7-bit groups with the least significant group first. The bytes don't need to be aligned.
+
+		If the varint is longer than allowed by `Limits::max_varint_len`, an error of kind `QuotaExceeded` is returned. If the value doesn't fit into 64 bits, an error of kind `InvalidData` is returned. If an error occurs, the bytes read so far are consumed.
+
+		# Examples
+
+		```
+		# use endio_bit::BEBitReader;
+		let mut reader = BEBitReader::new(&b"\xac\x02"[..]);
+		assert_eq!(reader.read_varint().unwrap(), 300);
+		```
+	*/
+	pub fn read_varint(&mut self) -> Res<u64> {
+		let max_len = self.limits.max_varint_len.unwrap_or(u32::MAX);
+		let start = self.bit_count;
+		let mut value = 0;
+		let mut len = 0;
+		loop {
+			if len == max_len {
+				let error = Error::new(ErrorKind::QuotaExceeded, format!("varint longer than {} bytes", max_len));
+				return Err(BitError::new(start, (len + 1) * 8, &self.context, false, error).into());
+			}
+			let byte = self.read_bits(8)?;
+			let shift = len * 7;
+			if shift >= 64 || (shift == 63 && byte & 0x7e != 0) {
+				let error = Error::new(ErrorKind::InvalidData, "varint doesn't fit into 64 bits");
+				return Err(BitError::new(start, (len + 1) * 8, &self.context, false, error).into());
+			}
+			value |= ((byte & 0x7f) as u64) << shift;
+			len += 1;
+			if byte & 0x80 == 0 {
+				return Ok(value);
+			}
+		}
+	}
+
+	/**
+		Skips `n` bits, reading whole bytes in bulk from the underlying reader.
+
+		If the end of the data is reached, an error of kind `UnexpectedEof` is returned, stating the number of bits actually skipped, which are consumed. If the underlying reader fails with another error, the bits skipped so far are consumed as well, and the error wraps a `BitError` at the position reached, whose count is the number of bits left to skip. If the position after skipping doesn't fit into a `u64`, an error of kind `InvalidInput` is returned without skipping any bits. If the underlying reader supports seeking, `skip_bits_seek` avoids reading the skipped bytes.
+
+		# Examples
+
+		```
+		# use endio_bit::BEBitReader;
+		let mut reader = BEBitReader::new(&b"\x12\x34\x56"[..]);
+		reader.skip_bits(12).unwrap();
+		assert_eq!(reader.read_bits(8).unwrap(), 0x45);
+		let err = reader.skip_bits(100).unwrap_err();
+		assert_eq!(err.to_string(), "unexpected end of file after skipping 4 bits at bit 20 while reading 100 bits");
+		```
+	*/
+	pub fn skip_bits(&mut self, n: u64) -> Res<()> {
+		let mut buf = [0; 256];
+		self.skip_bits_with(n, |reader, bytes| {
+			let len = std::cmp::min(bytes, buf.len() as u64) as usize;
+			reader.read_inner(&mut buf[..len]).map(|len| len as u64)
+		})
+	}
+
+	/// Skips `n` bits, using `skip_bytes` to skip up to the given number of whole bytes of the underlying reader, which returns the number of bytes skipped, or 0 at the end of the data.
+	fn skip_bits_with(&mut self, n: u64, mut skip_bytes: impl FnMut(&mut Self, u64) -> Res<u64>) -> Res<()> {
+		let start = self.bit_count;
+		let count = std::cmp::min(n, u32::MAX as u64) as u32;
+		self.check_budget(n).map_err(|e| self.error(count, e))?;
+		let end = start.checked_add(n).ok_or_else(|| self.error(count, Error::new(ErrorKind::InvalidInput, "skip past the maximum bit position")))?;
+		if !self.is_aligned() {
+			let leading = std::cmp::min(self.buffered_bits() as u64, n) as u8;
+			self.bit_offset = (self.bit_offset + leading) % 8;
+			self.bit_count += leading as u64;
+		}
+		let eof = |reader: &Self| {
+			let error = Error::new(ErrorKind::UnexpectedEof, format!("unexpected end of file after skipping {} bits", reader.bit_count - start));
+			BitError::new(start, count, &reader.context, false, error).into()
+		};
+		while end - self.bit_count >= 8 {
+			let bytes = (end - self.bit_count) / 8;
+			match skip_bytes(self, bytes) {
+				Ok(0) => return Err(eof(self)),
+				Ok(skipped) => self.bit_count += skipped * 8,
+				Err(e) if e.kind() == ErrorKind::Interrupted => {}
+				Err(e) => return Err(self.error((end - self.bit_count).min(u32::MAX as u64) as u32, e)),
+			}
+		}
+		let trailing = (end - self.bit_count) as u8;
+		if trailing > 0 {
+			self.bit_buffer = match self.read_byte() {
+				Ok(byte) => byte,
+				Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Err(eof(self)),
+				Err(e) => return Err(self.error(trailing as u32, e)),
+			};
+			self.bit_offset = trailing;
+			self.bit_count += trailing as u64;
+		}
+		Ok(())
+	}
+
+	/**
+		Creates a checkpoint at the current position, to return to it with `restore`, e.g. when parsing speculatively.
+
+		From now on, the bytes read from the underlying reader are kept in a replay buffer, until all checkpoints are released with `release`. Checkpoints can be nested. If the underlying reader supports seeking, `checkpoint_seek` avoids the replay buffer.
+
+		While data is kept for replaying, the underlying reader doesn't reflect the position of the `BitReader`, so it shouldn't be accessed through `get_mut`.
+
+		# Examples
+
+		```
+		use endio_bit::BEBitReader;
+
+		let mut reader = BEBitReader::new(&b"\x5a\xc3"[..]);
+		let checkpoint = reader.checkpoint();
+		assert_eq!(reader.read_bits(4).unwrap(), 0x05);
+		assert!(reader.read_varint().is_err());
+		reader.restore(&checkpoint).unwrap();
+		assert_eq!(reader.read_bits(3).unwrap(), 0x02);
+		reader.release(checkpoint);
+		assert_eq!(reader.read_bits(8).unwrap(), 0xd6);
+		```
+	*/
+	pub fn checkpoint(&mut self) -> Checkpoint {
+		self.checkpoints += 1;
+		self.make_checkpoint(CheckpointSource::Replay(self.replay_pos))
+	}
+
+	/**
+		Returns to the position of a checkpoint created by `checkpoint`. The checkpoint stays active and can be restored again.
+
+		The bit position is reset to the one at the checkpoint, but the bits read again still count towards the bit budget. If the checkpoint was created by `checkpoint_seek`, or isn't an active checkpoint of this reader, an error of kind `InvalidInput` is returned, use `restore_seek` for the former.
+	*/
+	pub fn restore(&mut self, checkpoint: &Checkpoint) -> Res<()> {
+		match checkpoint.source {
+			CheckpointSource::Replay(replay_pos) => {
+				if self.checkpoints == 0 || replay_pos > self.replay.len() {
+					return Err(self.error(0, Error::new(ErrorKind::InvalidInput, "checkpoint isn't active in this reader")));
+				}
+				self.replay_pos = replay_pos;
+				self.restore_state(checkpoint);
+				Ok(())
+			}
+			CheckpointSource::Seek(_) => Err(self.error(0, Error::new(ErrorKind::InvalidInput, "checkpoint was created by checkpoint_seek, use restore_seek"))),
+		}
+	}
+
+	/// Releases a checkpoint, freeing the replay buffer once no checkpoints are active and the data to be read again has been read.
+	pub fn release(&mut self, checkpoint: Checkpoint) {
+		if let CheckpointSource::Replay(_) = checkpoint.source {
+			self.checkpoints = self.checkpoints.saturating_sub(1);
+			self.trim_replay();
+		}
+	}
+
+	fn make_checkpoint(&self, source: CheckpointSource) -> Checkpoint {
+		Checkpoint {
+			bit_offset: self.bit_offset,
+			bit_buffer: self.bit_buffer,
+			bit_count: self.bit_count,
+			source,
+		}
+	}
+
+	fn restore_state(&mut self, checkpoint: &Checkpoint) {
+		self.restored_bits = self.restored_bits.saturating_add(self.bit_count.saturating_sub(checkpoint.bit_count));
+		self.bit_offset = checkpoint.bit_offset;
+		self.bit_buffer = checkpoint.bit_buffer;
+		self.bit_count = checkpoint.bit_count;
+	}
+}
+
+impl<E: BitEndianness, R: Read + Seek> BitReader<E, R> {
+	/**
+		Skips `n` bits like `skip_bits`, but seeks over the whole bytes instead of reading them.
+
+		The length of the underlying data is determined by seeking to its end, so that reaching the end results in the same error as with `skip_bits`. If seeking fails, the position of the underlying reader is unspecified.
+
+		While there are checkpoints created with `checkpoint` or data left to replay, the bytes are read like with `skip_bits`, so that they can be replayed.
+
+		# Examples
+
+		```
+		# use endio_bit::BEBitReader;
+		use std::io::Cursor;
+
+		let mut reader = BEBitReader::new(Cursor::new(vec![0; 1 << 20]));
+		reader.skip_bits(3).unwrap();
+		reader.skip_bits_seek(8 << 19).unwrap();
+		assert_eq!(reader.get_ref().position(), 1 + (1 << 19));
+		```
+	*/
+	pub fn skip_bits_seek(&mut self, n: u64) -> Res<()> {
+		if self.checkpoints > 0 || self.replay_pos < self.replay.len() {
+			return self.skip_bits(n);
+		}
+		self.skip_bits_with(n, |reader, bytes| {
+			let inner = &mut reader.inner;
+			let pos = inner.stream_position()?;
+			let len = inner.seek(SeekFrom::End(0))?;
+			let target = std::cmp::max(pos, std::cmp::min(pos.saturating_add(bytes), len));
+			inner.seek(SeekFrom::Start(target))?;
+			Ok(target - pos)
+		})
+	}
+
+	/**
+		Creates a checkpoint at the current position like `checkpoint`, but records the position of the underlying reader to seek back to it with `restore_seek`, instead of keeping the data in a replay buffer.
+
+		The checkpoint doesn't need to be released.
+
+		# Examples
+
+		```
+		use endio_bit::BEBitReader;
+		use std::io::Cursor;
+
+		let mut reader = BEBitReader::new(Cursor::new(b"\x5a\xc3"));
+		assert_eq!(reader.read_bits(4).unwrap(), 0x05);
+		let checkpoint = reader.checkpoint_seek().unwrap();
+		assert_eq!(reader.read_bits(8).unwrap(), 0xac);
+		reader.restore_seek(&checkpoint).unwrap();
+		assert_eq!(reader.read_bits(4).unwrap(), 0x0a);
+		```
+	*/
+	pub fn checkpoint_seek(&mut self) -> Res<Checkpoint> {
+		let pending = (self.replay.len() - self.replay_pos) as u64;
+		let position = self.inner.stream_position().map_err(|e| self.error(0, e))? - pending;
+		Ok(self.make_checkpoint(CheckpointSource::Seek(position)))
+	}
+
+	/**
+		Returns to the position of a checkpoint by seeking the underlying reader, or like `restore` if the checkpoint was created by `checkpoint`.
+
+		Seeking discards the replay buffer, so if checkpoints created by `checkpoint` are active, an error of kind `InvalidInput` is returned.
+	*/
+	pub fn restore_seek(&mut self, checkpoint: &Checkpoint) -> Res<()> {
+		match checkpoint.source {
+			CheckpointSource::Replay(_) => self.restore(checkpoint),
+			CheckpointSource::Seek(position) => {
+				if self.checkpoints > 0 {
+					return Err(self.error(0, Error::new(ErrorKind::InvalidInput, "can't seek while checkpoints with replay buffer are active")));
+				}
+				self.inner.seek(SeekFrom::Start(position)).map_err(|e| self.error(0, e))?;
+				self.replay = vec![];
+				self.replay_pos = 0;
+				self.restore_state(checkpoint);
+				Ok(())
+			}
+		}
+	}
+}
+
+/// A position in the stream of a `BitReader` to return to, created by `BitReader::checkpoint` or `BitReader::checkpoint_seek`.
+#[derive(Debug)]
+#[must_use = "checkpoints created by `checkpoint` keep the replay buffer growing until they are released"]
+pub struct Checkpoint {
+	bit_offset: u8,
+	bit_buffer: u8,
+	bit_count: u64,
+	source: CheckpointSource,
+}
+
+/// Where the bytes after a checkpoint are read from when restoring it.
+#[derive(Debug)]
+enum CheckpointSource {
+	/// Position in the replay buffer.
+	Replay(usize),
+	/// Position of the underlying reader.
+	Seek(u64),
+}
+
+/// Returns an error of kind `InvalidInput` if `count` exceeds 8, the maximum number of bits that can be read or written at once.
+pub(crate) fn check_bit_count(count: u8) -> Res<()> {
+	if count > 8 {
+		return Err(Error::new(ErrorKind::InvalidInput, format!("can't read or write {} bits at once, the maximum is 8", count)));
+	}
+	Ok(())
+}
+
+impl<E: BitEndianness, R: Read> BitRead for BitReader<E, R> {
+	fn read_bit(&mut self) -> Res<bool> {
+		BitReader::read_bit(self)
+	}
+
+	fn msb_first(&self) -> bool {
+		E::msb_first()
+	}
+
+	fn read_bits(&mut self, count: u8) -> Res<u8> {
+		BitReader::read_bits(self, count)
+	}
 }
 
 /**
@@ -202,16 +923,39 @@ impl<E: BitEndianness, R: Read> BitReader<E, R> {
 
 	Directly maps to [`Read`] for aligned reads.
 
+	For unaligned reads, each byte returned is composed of the remaining bits of the previous byte and the leading bits of the next byte. Therefore, when the end of the underlying data is reached, the remaining bits of the last byte can't be returned as a whole byte. Use `buffered_bits` to determine how many bits are left, and read them with `read_bits`.
+
 	[`Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
 */
 impl<E: BitEndianness, R: Read> Read for BitReader<E, R> {
 	fn read(&mut self, buf: &mut [u8]) -> Res<usize> {
-		let count_read = self.inner.read(buf)?;
+		let mut len = buf.len();
+		if let Some(end) = self.section_end {
+			len = std::cmp::min(len as u64, end.saturating_sub(self.bit_count) / 8) as usize;
+			if len == 0 {
+				return Ok(0);
+			}
+		}
+		if let Some(max_bits) = self.limits.max_bits {
+			len = std::cmp::min(len as u64, max_bits.saturating_sub(self.budget_used()) / 8) as usize;
+			if len == 0 && !buf.is_empty() {
+				return Err(Error::new(ErrorKind::QuotaExceeded, format!("bit budget of {} bits exceeded", max_bits)));
+			}
+		}
+		let buf = &mut buf[..len];
+		let count_read = loop {
+			match self.read_inner(buf) {
+				Ok(count_read) => break count_read,
+				Err(e) if e.kind() == ErrorKind::Interrupted => {}
+				Err(e) => return Err(e),
+			}
+		};
+		self.bit_count += count_read as u64 * 8;
 		if self.is_aligned() {
 			return Ok(count_read);
 		}
 		let mut last_byte = self.bit_buffer;
-		for b in buf.iter_mut() {
+		for b in buf[..count_read].iter_mut() {
 			let current_byte = *b;
 			*b = E::shift_msb(last_byte, self.bit_offset) | E::shift_lsb(current_byte, 8 - self.bit_offset);
 			last_byte = current_byte;
@@ -221,10 +965,116 @@ impl<E: BitEndianness, R: Read> Read for BitReader<E, R> {
 	}
 }
 
+/**
+	A section of a `BitReader` limited to a number of bits, created by `BitReader::take_bits`.
+
+	Derefs to the `BitReader`, so all of its methods can be used. Reading past the end of the section fails with an error of kind `UnexpectedEof`, as if the underlying data ended there, and the `Read` implementation returns `Ok(0)` once fewer than 8 bits are left. Note that `align` discards the bits up to the byte boundary even if they extend past the end of the section.
+
+	When the section is dropped, the rest of it is skipped and errors are ignored, unless disabled with `set_skip_on_drop`. Use `skip_to_end` to handle the errors.
+
+	Sections can be nested by calling `take_bits` on a section. Nested sections end no later than the enclosing section.
+*/
+#[derive(Debug)]
+pub struct TakeBits<'a, E: BitEndianness, R: Read> {
+	reader: &'a mut BitReader<E, R>,
+	/// End of the enclosing section, restored on drop.
+	outer_end: Option<u64>,
+	skip_on_drop: bool,
+}
+
+impl<E: BitEndianness, R: Read> TakeBits<'_, E, R> {
+	/// Returns the number of bits left in the section.
+	pub fn remaining_bits(&self) -> u64 {
+		self.end().saturating_sub(self.reader.bit_count)
+	}
+
+	/// Sets whether the rest of the section is skipped when it's dropped. Defaults to `true`.
+	pub fn set_skip_on_drop(&mut self, skip_on_drop: bool) {
+		self.skip_on_drop = skip_on_drop;
+	}
+
+	/// Skips the rest of the section, returning any errors. If an error occurs, the bits skipped so far are consumed.
+	pub fn skip_to_end(mut self) -> Res<()> {
+		self.skip_on_drop = false;
+		let remaining = self.remaining_bits();
+		self.reader.skip_bits(remaining)
+	}
+
+	fn end(&self) -> u64 {
+		self.reader.section_end.expect("section end is set while a section exists")
+	}
+}
+
+impl<E: BitEndianness, R: Read> Deref for TakeBits<'_, E, R> {
+	type Target = BitReader<E, R>;
+
+	fn deref(&self) -> &Self::Target {
+		self.reader
+	}
+}
+
+impl<E: BitEndianness, R: Read> DerefMut for TakeBits<'_, E, R> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.reader
+	}
+}
+
+impl<E: BitEndianness, R: Read> BitRead for TakeBits<'_, E, R> {
+	fn read_bit(&mut self) -> Res<bool> {
+		self.reader.read_bit()
+	}
+
+	fn msb_first(&self) -> bool {
+		E::msb_first()
+	}
+
+	fn read_bits(&mut self, count: u8) -> Res<u8> {
+		self.reader.read_bits(count)
+	}
+}
+
+impl<E: BitEndianness, R: Read> Read for TakeBits<'_, E, R> {
+	fn read(&mut self, buf: &mut [u8]) -> Res<usize> {
+		self.reader.read(buf)
+	}
+}
+
+impl<E: BitEndianness, R: Read> Drop for TakeBits<'_, E, R> {
+	fn drop(&mut self) {
+		if self.skip_on_drop {
+			let remaining = self.remaining_bits();
+			let _ = self.reader.skip_bits(remaining);
+		}
+		self.reader.section_end = self.outer_end;
+	}
+}
+
 #[cfg(test)]
 mod tests_common {
-	use std::io::Read;
-	use crate::BEBitReader;
+	use std::io::{Cursor, ErrorKind, Read};
+	use crate::{BEBitReader, BitError, BitRead, Limits, Padding};
+	use crate::test_util::{retry, ErrorReader, ShortReader};
+
+	#[test]
+	fn would_block() {
+		let mut reader = BEBitReader::new(ErrorReader::new(b"\xab\xcd", ErrorKind::WouldBlock));
+		assert_eq!(retry(|| reader.read_bits(3)), 0x05);
+		assert_eq!(retry(|| reader.read_bits(8)), 0x5e);
+		assert_eq!(retry(|| reader.read_bit()), false);
+		assert_eq!(retry(|| reader.read_bits(4)), 0x0d);
+		assert!(reader.is_aligned());
+	}
+
+	#[test]
+	fn interrupted() {
+		let mut reader = BEBitReader::new(ErrorReader::new(b"\xab\xcd\xef", ErrorKind::Interrupted));
+		assert_eq!(reader.read_bits(4).unwrap(), 0x0a);
+		let mut buf = [0; 2];
+		assert_eq!(reader.read(&mut buf).unwrap(), 1);
+		assert_eq!(reader.read(&mut buf[1..]).unwrap(), 1);
+		assert_eq!(&buf, b"\xbc\xde");
+		assert_eq!(reader.read_bits(4).unwrap(), 0x0f);
+	}
 
 	#[test]
 	fn get_ref() {
@@ -238,7 +1088,7 @@ mod tests_common {
 		let mut reader = BEBitReader::new(&b"\xf8"[..]);
 		let inner = reader.get_mut();
 		let mut buf = [0; 1];
-		inner.read(&mut buf).unwrap();
+		inner.read_exact(&mut buf).unwrap();
 		assert_eq!(buf[0], 0xf8);
 	}
 
@@ -251,13 +1101,24 @@ mod tests_common {
 		reader.get_mut();
 	}
 
+	#[test]
+	fn try_get_mut() {
+		let mut reader = BEBitReader::new(&b"\xff\x00"[..]);
+		assert!(reader.try_get_mut().is_some());
+		reader.read_bits(4).unwrap();
+		assert!(reader.try_get_mut().is_none());
+		reader.align();
+		assert_eq!(reader.try_get_mut().unwrap().len(), 1);
+		assert_eq!(reader.align_to(0).unwrap_err().kind(), ErrorKind::InvalidInput);
+	}
+
 	#[test]
 	fn get_mut_unchecked() {
 		let mut reader = BEBitReader::new(&b"\x00\xff"[..]);
 		reader.read_bits(4).unwrap();
 		let inner = unsafe { reader.get_mut_unchecked() };
 		let mut buf = [0; 1];
-		inner.read(&mut buf).unwrap();
+		inner.read_exact(&mut buf).unwrap();
 		assert_eq!(buf[0], 0xff);
 	}
 
@@ -273,18 +1134,359 @@ mod tests_common {
 		let mut reader = BEBitReader::new(&b"\xf8\x80"[..]);
 		let bits = reader.read_bits(5).unwrap();
 		assert_eq!(reader.is_aligned(), false);
+		assert_eq!(reader.buffered_bits(), 3);
 		reader.align();
 		assert_eq!(reader.is_aligned(), true);
+		assert_eq!(reader.buffered_bits(), 0);
 		let bit = reader.read_bit().unwrap();
 		assert_eq!(bits, 31);
 		assert_eq!(bit, true);
 	}
+
+	#[test]
+	fn bit_position() {
+		let mut reader = BEBitReader::new(ShortReader(b"\x12\x34\x56\x78\x9a"));
+		assert_eq!(reader.bit_position(), 0);
+		reader.read_bit().unwrap();
+		reader.set_mark();
+		let mut buf = [0; 3];
+		assert_eq!(reader.read(&mut buf).unwrap(), 1);
+		assert_eq!((reader.bit_position(), reader.bit_offset()), (9, 1));
+		let checkpoint = reader.checkpoint();
+		reader.read_bits(5).unwrap();
+		reader.align();
+		assert_eq!((reader.bit_position(), reader.bits_since_mark(), reader.bit_offset()), (16, 15, 0));
+		reader.skip_bits(9).unwrap();
+		assert_eq!((reader.bit_position(), reader.bit_offset()), (25, 1));
+		reader.restore(&checkpoint).unwrap();
+		assert_eq!((reader.bit_position(), reader.bits_since_mark(), reader.bit_offset()), (9, 8, 1));
+		reader.set_mark();
+		reader.release(checkpoint);
+		reader.read_bits(7).unwrap();
+		assert_eq!(reader.bits_since_mark(), 7);
+	}
+
+	#[test]
+	fn align_expect_zero() {
+		let mut reader = BEBitReader::new(&b"\xa0\xa1\xff"[..]);
+		assert_eq!(reader.read_bits(3).unwrap(), 0x05);
+		reader.align_expect_zero().unwrap();
+		assert_eq!(reader.read_bits(3).unwrap(), 0x05);
+		let err = reader.align_expect_zero().unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidData);
+		assert_eq!(err.to_string(), "expected padding 00000, found 00001 at bit 11 while reading 5 bits");
+		assert_eq!(reader.is_aligned(), true);
+		assert_eq!(reader.read_bits(8).unwrap(), 0xff);
+	}
+
+	#[test]
+	fn align_expect_stop_bit() {
+		let mut reader = BEBitReader::new(&b"\x80\x81"[..]);
+		reader.align_expect(Padding::StopBit).unwrap();
+		let err = reader.align_expect(Padding::StopBit).unwrap_err();
+		assert_eq!(err.to_string(), "expected padding 10000000, found 10000001 at bit 8 while reading 8 bits");
+	}
+
+	#[test]
+	fn align_to() {
+		let mut reader = BEBitReader::new(&b"\x12\x34\x56\x78\x9a"[..]);
+		assert_eq!(reader.read_bit().unwrap(), false);
+		reader.align_to(32).unwrap();
+		assert_eq!(reader.read_bits(8).unwrap(), 0x9a);
+		let mut reader = BEBitReader::new(&b"\x9f"[..]);
+		assert_eq!(reader.read_bit().unwrap(), true);
+		reader.align_to(3).unwrap();
+		assert_eq!(reader.read_bits(5).unwrap(), 0x1f);
+	}
+
+	#[test]
+	fn align_to_expect() {
+		let mut reader = BEBitReader::new(&b"\xf5\x55\x55\x55\x01"[..]);
+		assert_eq!(reader.read_bits(3).unwrap(), 0x07);
+		reader.align_to_expect(32, Padding::Pattern(0x55)).unwrap();
+		assert_eq!(reader.read_bits(8).unwrap(), 0x01);
+		let mut reader = BEBitReader::new(&b"\x80\x00\x10\x00"[..]);
+		assert_eq!(reader.read_bit().unwrap(), true);
+		let err = reader.align_to_expect(32, Padding::Zeros).unwrap_err();
+		assert_eq!(err.to_string(), "expected padding 00000000, found 00010000 at bit 16 while reading 8 bits");
+	}
+
+	#[test]
+	fn bit_budget() {
+		let mut reader = BEBitReader::new(&b"\x12\x34\x56"[..]);
+		reader.set_limits(Limits { max_bits: Some(12), ..Limits::default() });
+		assert_eq!(reader.read_bits(8).unwrap(), 0x12);
+		let err = reader.read_bits(5).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::QuotaExceeded);
+		assert_eq!(err.to_string(), "bit budget of 12 bits exceeded at bit 8 while reading 5 bits");
+		assert_eq!(reader.read_bits(4).unwrap(), 0x03);
+		assert_eq!(reader.read_bit().unwrap_err().kind(), ErrorKind::QuotaExceeded);
+		assert_eq!(reader.align_to(16).unwrap_err().kind(), ErrorKind::QuotaExceeded);
+	}
+
+	#[test]
+	fn bit_budget_read() {
+		let mut reader = BEBitReader::new(&b"\x12\x34\x56"[..]);
+		reader.set_limits(Limits { max_bits: Some(20), ..Limits::default() });
+		let mut buf = [0; 4];
+		assert_eq!(reader.read(&mut buf).unwrap(), 2);
+		assert_eq!(&buf[..2], b"\x12\x34");
+		assert_eq!(reader.read(&mut buf).unwrap_err().kind(), ErrorKind::QuotaExceeded);
+		assert_eq!(reader.read(&mut []).unwrap(), 0);
+		assert_eq!(reader.read_bits(4).unwrap(), 0x05);
+	}
+
+	#[test]
+	fn bit_budget_overflow() {
+		let mut reader = BEBitReader::new(&b"\x12\x34\x56"[..]);
+		reader.set_limits(Limits { max_bits: Some(100), ..Limits::default() });
+		reader.read_bit().unwrap();
+		assert_eq!(reader.skip_bits(u64::MAX).unwrap_err().kind(), ErrorKind::QuotaExceeded);
+		let mut section = reader.take_bits(4);
+		assert_eq!(section.skip_bits(u64::MAX).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+		assert_eq!(section.read_bits(4).unwrap(), 0x02);
+	}
+
+	#[test]
+	fn bit_read() {
+		fn read_nibbles(reader: &mut dyn BitRead) -> std::io::Result<(u8, u8)> {
+			Ok((reader.read_bits(4)?, reader.read_bits(4)?))
+		}
+		let mut reader = BEBitReader::new(&b"\x12\x34\x56"[..]);
+		assert_eq!(read_nibbles(&mut reader).unwrap(), (0x01, 0x02));
+		let mut section = reader.take_bits(4);
+		assert_eq!(read_nibbles(&mut section).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+		drop(section);
+		let mut boxed: Box<dyn BitRead> = Box::new(reader);
+		assert_eq!(read_nibbles(&mut boxed).unwrap(), (0x04, 0x05));
+	}
+
+	#[test]
+	fn take_bits() {
+		let mut reader = BEBitReader::new(&b"\x12\x34\x56\x78\x9a"[..]);
+		assert_eq!(reader.read_bits(4).unwrap(), 0x01);
+		{
+			let mut section = reader.take_bits(28);
+			assert_eq!(section.remaining_bits(), 28);
+			let mut buf = [0; 4];
+			assert_eq!(section.read(&mut buf).unwrap(), 3);
+			assert_eq!(&buf[..3], b"\x23\x45\x67");
+			assert_eq!(section.read(&mut buf).unwrap(), 0);
+			let err = section.read_bits(5).unwrap_err();
+			assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+			assert_eq!(err.to_string(), "unexpected end of section at bit 28 while reading 5 bits");
+			assert_eq!(section.read_bits(4).unwrap(), 0x08);
+			assert_eq!(section.remaining_bits(), 0);
+		}
+		assert_eq!(reader.read_bits(8).unwrap(), 0x9a);
+	}
+
+	#[test]
+	fn take_bits_nested() {
+		let mut reader = BEBitReader::new(&b"\x12\x34\x56\x78\x9a"[..]);
+		{
+			let mut outer = reader.take_bits(20);
+			assert_eq!(outer.read_bits(4).unwrap(), 0x01);
+			{
+				let mut inner = outer.take_bits(100);
+				assert_eq!(inner.remaining_bits(), 16);
+				assert_eq!(inner.read_bits(3).unwrap(), 0x01);
+				let mut innermost = inner.take_bits(6);
+				innermost.set_skip_on_drop(false);
+				assert_eq!(innermost.read_bit().unwrap(), false);
+			}
+			assert_eq!(outer.remaining_bits(), 0);
+			assert_eq!(outer.read_bit().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+		}
+		assert_eq!(reader.read_bits(4).unwrap(), 0x06);
+	}
+
+	#[test]
+	fn take_bits_skip_to_end() {
+		let mut reader = BEBitReader::new(&b"\xff\x00"[..]);
+		assert_eq!(reader.read_bits(3).unwrap(), 0x07);
+		let mut section = reader.take_bits(1000);
+		assert_eq!(section.read_bits(2).unwrap(), 0x03);
+		let err = section.skip_to_end().unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+		assert_eq!(err.to_string(), "unexpected end of file after skipping 11 bits at bit 5 while reading 998 bits");
+		assert_eq!(reader.read_bit().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+		let mut reader = BEBitReader::new(&b"\xff\x00\x0f"[..]);
+		assert_eq!(reader.read_bit().unwrap(), true);
+		reader.take_bits(18).skip_to_end().unwrap();
+		assert_eq!(reader.read_bits(5).unwrap(), 0x0f);
+	}
+
+	#[test]
+	fn skip_bits() {
+		let mut reader = BEBitReader::new(ShortReader(b"\x12\x34\x56\x78\x9a"));
+		reader.skip_bits(0).unwrap();
+		assert_eq!(reader.read_bits(3).unwrap(), 0x00);
+		reader.skip_bits(2).unwrap();
+		assert_eq!(reader.read_bits(3).unwrap(), 0x02);
+		reader.skip_bits(27).unwrap();
+		assert_eq!(reader.read_bits(5).unwrap(), 0x1a);
+		let err = reader.skip_bits(4).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+		assert_eq!(err.to_string(), "unexpected end of file after skipping 0 bits at bit 40 while reading 4 bits");
+		let mut reader = BEBitReader::new(ErrorReader::new(b"\x12\x34\x56", ErrorKind::WouldBlock));
+		assert_eq!(retry(|| reader.read_bits(4)), 0x01);
+		let err = reader.skip_bits(16).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::WouldBlock);
+		let err = BitError::from_io_error(&err).unwrap();
+		assert_eq!((err.position(), err.count()), (8, 12));
+		let err = reader.skip_bits(12).unwrap_err();
+		let err = BitError::from_io_error(&err).unwrap();
+		assert_eq!((err.position(), err.count(), reader.bit_position()), (16, 4, 16));
+		retry(|| reader.skip_bits(4));
+		assert_eq!(retry(|| reader.read_bits(4)), 0x06);
+		let mut reader = BEBitReader::new(&b"\xff"[..]);
+		reader.set_limits(Limits { max_bits: Some(4), ..Limits::default() });
+		assert_eq!(reader.skip_bits(5).unwrap_err().kind(), ErrorKind::QuotaExceeded);
+		assert_eq!(reader.read_bits(4).unwrap(), 0x0f);
+		let mut reader = BEBitReader::new(&b"\x12\x34"[..]);
+		reader.read_bit().unwrap();
+		let err = reader.skip_bits(u64::MAX).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidInput);
+		assert_eq!(BitError::from_io_error(&err).unwrap().position(), 1);
+		assert_eq!(reader.read_bits(7).unwrap(), 0x12);
+	}
+
+	#[test]
+	fn skip_bits_seek() {
+		let mut reader = BEBitReader::new(Cursor::new(&b"\x12\x34\x56\x78\x9a"[..]));
+		assert_eq!(reader.read_bits(3).unwrap(), 0x00);
+		reader.skip_bits_seek(18).unwrap();
+		assert_eq!(reader.get_ref().position(), 3);
+		assert_eq!(reader.read_bits(3).unwrap(), 0x06);
+		let err = reader.skip_bits_seek(64).unwrap_err();
+		assert_eq!(err.to_string(), "unexpected end of file after skipping 16 bits at bit 24 while reading 64 bits");
+		assert_eq!(reader.read_bit().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+	}
+
+	#[test]
+	fn checkpoint() {
+		let mut reader = BEBitReader::new(ErrorReader::new(b"\x12\x34\x56\x78", ErrorKind::WouldBlock));
+		assert_eq!(retry(|| reader.read_bits(4)), 0x01);
+		let outer = reader.checkpoint();
+		assert_eq!(retry(|| reader.read_bits(8)), 0x23);
+		let inner = reader.checkpoint();
+		let mut buf = [0; 1];
+		assert_eq!(retry(|| reader.read(&mut buf)), 1);
+		assert_eq!(buf[0], 0x45);
+		reader.restore(&inner).unwrap();
+		assert_eq!(retry(|| reader.read_bits(4)), 0x04);
+		reader.restore(&outer).unwrap();
+		reader.release(outer);
+		assert_eq!(retry(|| reader.read_bits(4)), 0x02);
+		retry(|| reader.skip_bits(12));
+		assert_eq!(retry(|| reader.read_bits(4)), 0x06);
+		reader.restore(&inner).unwrap();
+		reader.release(inner);
+		assert_eq!(retry(|| reader.read_bits(8)), 0x45);
+		assert_eq!(retry(|| reader.read_bits(8)), 0x67);
+		assert_eq!(retry(|| reader.read_bits(4)), 0x08);
+	}
+
+	#[test]
+	fn checkpoint_budget() {
+		let mut reader = BEBitReader::new(&b"\x12\x34"[..]);
+		reader.set_limits(Limits { max_bits: Some(12), ..Limits::default() });
+		let checkpoint = reader.checkpoint();
+		assert_eq!(reader.read_bits(8).unwrap(), 0x12);
+		reader.restore(&checkpoint).unwrap();
+		reader.release(checkpoint);
+		assert_eq!(reader.read_bits(4).unwrap(), 0x01);
+		assert_eq!(reader.read_bit().unwrap_err().kind(), ErrorKind::QuotaExceeded);
+		assert_eq!(reader.bit_position(), 4);
+	}
+
+	#[test]
+	fn checkpoint_foreign() {
+		let mut other = BEBitReader::new(&b"\x12\x34"[..]);
+		let checkpoint = other.checkpoint();
+		other.read_bits(8).unwrap();
+		let foreign = other.checkpoint();
+		let mut reader = BEBitReader::new(&b"\x56"[..]);
+		assert_eq!(reader.restore(&foreign).unwrap_err().kind(), ErrorKind::InvalidInput);
+		let own = reader.checkpoint();
+		assert_eq!(reader.restore(&foreign).unwrap_err().kind(), ErrorKind::InvalidInput);
+		reader.release(own);
+		other.release(foreign);
+		other.release(checkpoint);
+		assert_eq!(reader.read_bits(8).unwrap(), 0x56);
+	}
+
+	#[test]
+	fn checkpoint_seek() {
+		let mut reader = BEBitReader::new(Cursor::new(&b"\x12\x34\x56\x78"[..]));
+		assert_eq!(reader.read_bits(4).unwrap(), 0x01);
+		let replay = reader.checkpoint();
+		assert_eq!(reader.read_bits(8).unwrap(), 0x23);
+		let seek = reader.checkpoint_seek().unwrap();
+		reader.skip_bits_seek(12).unwrap();
+		assert_eq!(reader.restore_seek(&seek).unwrap_err().kind(), ErrorKind::InvalidInput);
+		reader.restore_seek(&replay).unwrap();
+		reader.release(replay);
+		assert_eq!(reader.read_bits(4).unwrap(), 0x02);
+		let err = reader.restore(&seek).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidInput);
+		reader.restore_seek(&seek).unwrap();
+		assert_eq!(reader.read_bits(8).unwrap(), 0x45);
+		assert_eq!(reader.get_ref().position(), 3);
+	}
+
+	#[test]
+	fn checkpoint_skip_bits_seek() {
+		let mut reader = BEBitReader::new(Cursor::new(&b"\x12\x34\x56"[..]));
+		let checkpoint = reader.checkpoint();
+		reader.skip_bits_seek(16).unwrap();
+		reader.restore(&checkpoint).unwrap();
+		reader.release(checkpoint);
+		assert_eq!(reader.read_bits(8).unwrap(), 0x12);
+		reader.skip_bits_seek(8).unwrap();
+		assert_eq!(reader.read_bits(8).unwrap(), 0x56);
+	}
+
+	#[test]
+	fn read_unary() {
+		let mut reader = BEBitReader::new(&b"\xf0\x0f"[..]);
+		assert_eq!(reader.read_unary(false).unwrap(), 4);
+		assert_eq!(reader.read_unary(true).unwrap(), 7);
+		let mut reader = BEBitReader::new(&b"\xf0\x0f"[..]);
+		reader.set_limits(Limits { max_unary_len: Some(4), ..Limits::default() });
+		assert_eq!(reader.read_unary(false).unwrap(), 4);
+		let err = reader.read_unary(true).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::QuotaExceeded);
+		assert_eq!(err.to_string(), "unary code longer than 4 bits at bit 5 while reading 5 bits");
+	}
+
+	#[test]
+	fn read_varint() {
+		let mut reader = BEBitReader::new(&b"\xaa\xc0\x20"[..]);
+		assert_eq!(reader.read_bits(4).unwrap(), 0x0a);
+		assert_eq!(reader.read_varint().unwrap(), 300);
+		let mut reader = BEBitReader::new(&b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01\xff\xff\xff\xff\xff\xff\xff\xff\xff\x02"[..]);
+		assert_eq!(reader.read_varint().unwrap(), u64::MAX);
+		assert_eq!(reader.read_varint().unwrap_err().kind(), ErrorKind::InvalidData);
+		let mut reader = BEBitReader::new(&b"\x80\x80\x80\x80\x80\x80\x80\x80\x80\x80\x00"[..]);
+		assert_eq!(reader.read_varint().unwrap_err().kind(), ErrorKind::InvalidData);
+		let mut reader = BEBitReader::new(&b"\x96\x01\xff\xff\x01"[..]);
+		reader.set_limits(Limits { max_varint_len: Some(2), ..Limits::default() });
+		assert_eq!(reader.read_varint().unwrap(), 150);
+		let err = reader.read_varint().unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::QuotaExceeded);
+		let bit_error = BitError::from_io_error(&err).unwrap();
+		assert_eq!(bit_error.position(), 16);
+		assert_eq!(bit_error.count(), 24);
+	}
 }
 
 #[cfg(test)]
 mod tests_be {
-	use std::io::Read;
+	use std::io::{ErrorKind, Read};
 	use crate::BEBitReader;
+	use crate::test_util::ShortReader;
 
 	#[test]
 	fn read_aligned() {
@@ -309,6 +1511,23 @@ mod tests_be {
 		let mut buf = [0; 7];
 		assert_eq!(reader.read(&mut buf).unwrap(), 3);
 		assert_eq!(&buf, b"est\0\0\0\0");
+		assert_eq!(reader.buffered_bits(), 5);
+		assert_eq!(reader.read_bits(5).unwrap(), 0);
+	}
+
+	#[test]
+	fn read_shifted_short() {
+		let mut reader = BEBitReader::new(ShortReader(&b"\xaa\x8c\xae\x6e\x80"[..]));
+		assert_eq!(reader.read_bits(3).unwrap(), 5);
+		let mut buf = [0xff; 4];
+		assert_eq!(reader.read(&mut buf).unwrap(), 1);
+		assert_eq!(&buf, b"T\xff\xff\xff");
+		assert_eq!(reader.read_bits(8).unwrap(), b'e');
+		let mut buf = [0; 4];
+		reader.read_exact(&mut buf[..2]).unwrap();
+		assert_eq!(&buf, b"st\0\0");
+		assert_eq!(reader.read(&mut buf).unwrap(), 0);
+		assert_eq!(reader.buffered_bits(), 5);
 	}
 
 	#[test]
@@ -332,17 +1551,19 @@ mod tests_be {
 	}
 
 	#[test]
-	#[should_panic]
 	fn read_too_many_bits() {
-		let mut reader = BEBitReader::new(&b""[..]);
-		let _ = reader.read_bits(9);
+		let mut reader = BEBitReader::new(&b"\xff"[..]);
+		assert_eq!(reader.read_bits(9).unwrap_err().kind(), ErrorKind::InvalidInput);
+		assert_eq!(reader.read_bits(0).unwrap(), 0);
+		assert_eq!(reader.read_bits(8).unwrap(), 0xff);
 	}
 }
 
 #[cfg(test)]
 mod tests_le {
-	use std::io::Read;
-	use crate::LEBitReader;
+	use std::io::{ErrorKind, Read};
+	use crate::{LEBitReader, Padding};
+	use crate::test_util::ShortReader;
 
 	#[test]
 	fn read_aligned() {
@@ -366,7 +1587,22 @@ mod tests_le {
 		assert_eq!(&buf, b"\x95");
 		let mut buf = [0; 7];
 		assert_eq!(reader.read(&mut buf).unwrap(), 3);
-		assert_eq!(&buf, b"\xd1\xd5\x0d\x10\0\0\0");
+		assert_eq!(&buf, b"\xd1\xd5\x0d\0\0\0\0");
+		assert_eq!(reader.buffered_bits(), 5);
+		assert_eq!(reader.read_bits(5).unwrap(), 0x10);
+	}
+
+	#[test]
+	fn read_shifted_short() {
+		let mut reader = LEBitReader::new(ShortReader(&b"\xaa\x8c\xae\x6e\x80"[..]));
+		assert_eq!(reader.read_bits(3).unwrap(), 2);
+		let mut buf = [0; 4];
+		assert_eq!(reader.read(&mut buf).unwrap(), 1);
+		assert_eq!(&buf, b"\x95\0\0\0");
+		reader.read_exact(&mut buf[..3]).unwrap();
+		assert_eq!(&buf, b"\xd1\xd5\x0d\0");
+		assert_eq!(reader.read(&mut buf).unwrap(), 0);
+		assert_eq!(reader.read_bits(5).unwrap(), 0x10);
 	}
 
 	#[test]
@@ -390,9 +1626,20 @@ mod tests_le {
 	}
 
 	#[test]
-	#[should_panic]
 	fn read_too_many_bits() {
-		let mut reader = LEBitReader::new(&b""[..]);
-		let _ = reader.read_bits(9);
+		let mut reader = LEBitReader::new(&b"\xff"[..]);
+		assert_eq!(reader.read_bits(9).unwrap_err().kind(), ErrorKind::InvalidInput);
+		assert_eq!(reader.read_bits(0).unwrap(), 0);
+		assert_eq!(reader.read_bits(8).unwrap(), 0xff);
+	}
+
+	#[test]
+	fn align_to_expect() {
+		let mut reader = LEBitReader::new(&b"\x57\x55\x03"[..]);
+		assert_eq!(reader.read_bits(3).unwrap(), 0x07);
+		reader.align_to_expect(16, Padding::Pattern(0x55)).unwrap();
+		assert_eq!(reader.read_bits(2).unwrap(), 0x03);
+		let err = reader.align_expect(Padding::StopBit).unwrap_err();
+		assert_eq!(err.to_string(), "expected padding 100000, found 000000 at bit 18 while reading 6 bits");
 	}
 }
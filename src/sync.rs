@@ -0,0 +1,190 @@
+use std::io::Result as Res;
+use std::io::{Error, ErrorKind, Read};
+
+use crate::endian::BitEndianness;
+use crate::read::BitReader;
+
+/// A match found by `BitReader::find_sync`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyncMatch {
+	/// Number of bits skipped before the match.
+	pub skipped: u64,
+	/// Number of bits in which the match differs from the pattern.
+	pub distance: u32,
+	/// The matched bits, in the same layout as the pattern.
+	pub bits: Vec<u8>,
+}
+
+/// Shift register of an arbitrary number of bits, with the most recent bit in the least significant position.
+struct Window {
+	/// Words of the register, most significant first.
+	words: Vec<u64>,
+	/// Mask of the valid bits in the most significant word.
+	top_mask: u64,
+	len: usize,
+}
+
+impl Window {
+	fn new(len: usize) -> Self {
+		let word_count = len.div_ceil(64);
+		let top_bits = len - (word_count - 1) * 64;
+		Self {
+			words: vec![0; word_count],
+			top_mask: u64::MAX >> (64 - top_bits),
+			len,
+		}
+	}
+
+	fn push(&mut self, bit: bool) {
+		let word_count = self.words.len();
+		for i in 0..word_count {
+			let carry = if i + 1 < word_count { self.words[i + 1] >> 63 } else { bit as u64 };
+			self.words[i] = self.words[i] << 1 | carry;
+		}
+		self.words[0] &= self.top_mask;
+	}
+
+	fn distance(&self, other: &Window) -> u32 {
+		self.words.iter().zip(&other.words).map(|(a, b)| (a ^ b).count_ones()).sum()
+	}
+
+	/// Returns the `index`th bit pushed, out of the last `len` bits.
+	fn bit(&self, index: usize) -> bool {
+		let pos = self.len - 1 - index;
+		let word = self.words.len() - 1 - pos / 64;
+		self.words[word] >> (pos % 64) & 1 != 0
+	}
+}
+
+impl<E: BitEndianness, R: Read> BitReader<E, R> {
+	/**
+		Searches for a bit pattern starting at any bit offset, and positions the reader directly after the first match.
+
+		The pattern consists of the first `len` bits of `pattern`, in the order they would be read from `pattern` by this reader. For example, the 12-bit ADTS sync word is specified as `&[0xff, 0xf0], 12` for a big endian reader. A match may differ from the pattern in up to `max_distance` bits (Hamming distance), in which case the actually matched bits can be found in the returned `SyncMatch`.
+
+		Since the matched bits have already been consumed when the match is found, the reader can't be positioned at the start of the match. Use `SyncMatch::bits` if the matched bits are part of the data to parse.
+
+		If the end of the data is reached before a match is found, an error of kind `UnexpectedEof` is returned, wrapping a `BitError` at the position where the search started, with the number of bits skipped in its description. Other errors of the underlying reader are passed on, with the bits read so far consumed. Since the bits already compared are lost, calling `find_sync` again after such an error may miss a match overlapping them, so the position is unspecified with respect to the pattern. If `len` is 0 or greater than the number of bits in `pattern`, an error of kind `InvalidInput` is returned.
+
+		# Examples
+
+		```
+		use endio_bit::BEBitReader;
+
+		let mut reader = BEBitReader::new(&b"\x03\xfb\xf9"[..]);
+		let sync = reader.find_sync(&[0xff, 0xf0], 12, 1).unwrap();
+		assert_eq!(sync.skipped, 6);
+		assert_eq!(sync.distance, 1);
+		assert_eq!(sync.bits, b"\xfe\xf0");
+		assert_eq!(reader.read_bits(6).unwrap(), 0x39);
+		```
+	*/
+	pub fn find_sync(&mut self, pattern: &[u8], len: usize, max_distance: u32) -> Res<SyncMatch> {
+		if len == 0 || len > pattern.len() * 8 {
+			return Err(Error::new(ErrorKind::InvalidInput, "invalid sync pattern length"));
+		}
+		let mut expected = Window::new(len);
+		for i in 0..len {
+			expected.push(pattern[i / 8] & E::nth_bit_mask((i % 8) as u8, 8) != 0);
+		}
+		let start = self.bit_position();
+		let mut window = Window::new(len);
+		let mut count_read: u64 = 0;
+		loop {
+			let bit = match self.read_bit() {
+				Ok(bit) => bit,
+				Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+					let skipped = count_read.saturating_sub(len as u64 - 1);
+					let error = Error::new(ErrorKind::UnexpectedEof, format!("sync pattern not found, skipped {} bits", skipped));
+					return Err(self.error_at(start, len.min(u32::MAX as usize) as u32, error));
+				}
+				Err(e) => return Err(e),
+			};
+			window.push(bit);
+			count_read += 1;
+			if count_read < len as u64 {
+				continue;
+			}
+			let distance = window.distance(&expected);
+			if distance <= max_distance {
+				let mut bits = vec![0; len.div_ceil(8)];
+				for i in 0..len {
+					if window.bit(i) {
+						bits[i / 8] |= E::nth_bit_mask((i % 8) as u8, 8);
+					}
+				}
+				return Ok(SyncMatch { skipped: count_read - len as u64, distance, bits });
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::ErrorKind;
+	use crate::{BEBitReader, BitError, LEBitReader};
+
+	#[test]
+	fn find_aligned() {
+		let mut reader = BEBitReader::new(&b"\x00\x47\x12"[..]);
+		let sync = reader.find_sync(b"\x47", 8, 0).unwrap();
+		assert_eq!(sync.skipped, 8);
+		assert_eq!(sync.distance, 0);
+		assert!(reader.is_aligned());
+		assert_eq!(reader.read_bits(8).unwrap(), 0x12);
+	}
+
+	#[test]
+	fn find_shifted() {
+		let mut reader = BEBitReader::new(&b"\x0f\xc0"[..]);
+		let sync = reader.find_sync(b"\x7e", 8, 0).unwrap();
+		assert_eq!(sync.skipped, 3);
+		assert_eq!(sync.bits, b"\x7e");
+		assert_eq!(reader.read_bits(5).unwrap(), 0x00);
+	}
+
+	#[test]
+	fn find_le() {
+		let mut reader = LEBitReader::new(&b"\xf8\x01"[..]);
+		let sync = reader.find_sync(b"\x7e", 8, 0).unwrap();
+		assert_eq!(sync.skipped, 2);
+		assert_eq!(reader.read_bits(6).unwrap(), 0x00);
+	}
+
+	#[test]
+	fn find_long() {
+		// 72-bit pattern spanning two words, shifted by 5 bits
+		let pattern = b"\x12\x34\x56\x78\x9a\xbc\xde\xf0\x11";
+		let mut data = vec![0xa8];
+		for window in pattern.windows(2) {
+			data.push(window[0] << 3 | window[1] >> 5);
+		}
+		data.push(pattern[8] << 3);
+		let mut reader = BEBitReader::new(&data[..]);
+		let sync = reader.find_sync(pattern, 72, 0).unwrap();
+		assert_eq!(sync.skipped, 5);
+		assert_eq!(&sync.bits[..], &pattern[..]);
+	}
+
+	#[test]
+	fn distance() {
+		let mut reader = BEBitReader::new(&b"\x46\x47"[..]);
+		assert_eq!(reader.find_sync(b"\x47", 8, 1).unwrap().skipped, 0);
+		let mut reader = BEBitReader::new(&b"\x44\x47"[..]);
+		assert_eq!(reader.find_sync(b"\x47", 8, 1).unwrap().skipped, 8);
+	}
+
+	#[test]
+	fn not_found() {
+		let mut reader = BEBitReader::new(&b"\x00\x00"[..]);
+		reader.read_bit().unwrap();
+		let err = reader.with_context("sync", |reader| reader.find_sync(b"\xff", 4, 0)).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+		assert!(err.to_string().contains("skipped 12 bits"));
+		let bit_error = BitError::from_io_error(&err).unwrap();
+		assert_eq!((bit_error.position(), bit_error.count()), (1, 4));
+		assert_eq!(bit_error.context(), ["sync"]);
+		let mut reader = BEBitReader::new(&b"\x00"[..]);
+		assert_eq!(reader.find_sync(b"\xff", 9, 0).unwrap_err().kind(), ErrorKind::InvalidInput);
+	}
+}
@@ -0,0 +1,95 @@
+use std::io::{ErrorKind, Read, Write};
+
+/// Returns at most one byte per read.
+pub struct ShortReader<'a>(pub &'a [u8]);
+
+impl Read for ShortReader<'_> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let len = std::cmp::min(buf.len(), std::cmp::min(self.0.len(), 1));
+		buf[..len].copy_from_slice(&self.0[..len]);
+		self.0 = &self.0[len..];
+		Ok(len)
+	}
+}
+
+/// Returns an error of the specified kind before every byte.
+pub struct ErrorReader<'a> {
+	data: &'a [u8],
+	kind: ErrorKind,
+	fail: bool,
+}
+
+impl<'a> ErrorReader<'a> {
+	pub fn new(data: &'a [u8], kind: ErrorKind) -> Self {
+		Self { data, kind, fail: false }
+	}
+}
+
+impl Read for ErrorReader<'_> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		self.fail = !self.fail;
+		if self.fail {
+			return Err(self.kind.into());
+		}
+		let len = std::cmp::min(buf.len(), std::cmp::min(self.data.len(), 1));
+		buf[..len].copy_from_slice(&self.data[..len]);
+		self.data = &self.data[len..];
+		Ok(len)
+	}
+}
+
+/// Writer accepting at most one byte per call.
+#[derive(Debug)]
+pub struct ShortWriter(pub Vec<u8>);
+
+impl Write for ShortWriter {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		let len = std::cmp::min(buf.len(), 1);
+		self.0.extend_from_slice(&buf[..len]);
+		Ok(len)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Returns an error of the specified kind before every byte.
+#[derive(Debug)]
+pub struct ErrorWriter {
+	pub data: Vec<u8>,
+	kind: ErrorKind,
+	fail: bool,
+}
+
+impl ErrorWriter {
+	pub fn new(kind: ErrorKind) -> Self {
+		Self { data: vec![], kind, fail: false }
+	}
+}
+
+impl Write for ErrorWriter {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.fail = !self.fail;
+		if self.fail {
+			return Err(self.kind.into());
+		}
+		let len = std::cmp::min(buf.len(), 1);
+		self.data.extend_from_slice(&buf[..len]);
+		Ok(len)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Repeats an operation until it doesn't fail with `WouldBlock`.
+pub fn retry<T>(mut op: impl FnMut() -> std::io::Result<T>) -> T {
+	loop {
+		match op() {
+			Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+			res => return res.unwrap(),
+		}
+	}
+}
@@ -1,7 +1,10 @@
+use std::borrow::Cow;
 use std::io::Result as Res;
-use std::io::Write;
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom, Write};
 
 use crate::endian::{BitEndianness, BE, LE};
+use crate::error::BitError;
+use crate::read::check_bit_count;
 
 /// Writes most significant bits first.
 pub type BEBitWriter<W> = BitWriter<BE, W>;
@@ -16,7 +19,7 @@ pub type LEBitWriter<W> = BitWriter<LE, W>;
 	See [`std::io::IntoInnerError`] for documentation.
 
 	[`std::io::IntoInnerError`]: https://doc.rust-lang.org/std/io/struct.IntoInnerError.html
-**/
+*/
 #[derive(Debug)]
 pub struct IntoInnerError<W>(W, std::io::Error);
 
@@ -25,12 +28,112 @@ impl<W> IntoInnerError<W> {
 	pub fn into_inner(self) -> W { self.0 }
 }
 
+/// Specifies the bits used to pad the stream on alignment, when writing with `BitWriter::set_padding` or validating with `BitReader::align_expect`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Padding {
+	/// Pad with 0-bits. This is the default.
+	Zeros,
+	/// Pad with 1-bits, as required e.g. by JPEG.
+	Ones,
+	/// Write a 1-bit followed by 0-bits, as in H.264 RBSP trailing bits. The 1-bit is always written, even if the stream is already aligned, unless no bits have been written since the last padding.
+	StopBit,
+	/// Pad with the bits at the same positions in the given byte, so that padding bytes are equal to it.
+	Pattern(u8),
+}
+
+/// Specifies what happens to a partially written byte when a `BitWriter` is dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DropPolicy {
+	/// Pad to the final alignment boundary and write out the partial byte, ignoring any errors. This is the default.
+	Align,
+	/// Discard the partial byte.
+	Discard,
+	/// Panic if the writer isn't at the end of the stream, aligned to the final alignment boundary, unless the thread is already panicking. Useful to find code paths that don't finish the writer.
+	Panic,
+}
+
+/**
+	A sink for bits, implemented by `BitWriter` and the bit-level writing adapters of this crate.
+
+	The trait is object safe, so serializers can be written once for any bit sink, taking `&mut dyn BitWrite` or a generic `impl BitWrite`.
+
+	# Examples
+
+	```
+	use endio_bit::{BEBitWriter, BitWrite, LEBitWriter};
+	use std::io::Result;
+
+	fn write_header(writer: &mut dyn BitWrite, flag: bool, kind: u8) -> Result<()> {
+		writer.write_bit(flag)?;
+		writer.write_bits(kind, 3)
+	}
+
+	let mut writer = BEBitWriter::new(vec![]);
+	write_header(&mut writer, true, 5).unwrap();
+	assert_eq!(writer.into_inner().unwrap(), b"\xd0");
+	let mut writer = LEBitWriter::new(vec![]);
+	write_header(&mut writer, true, 5).unwrap();
+	assert_eq!(writer.into_inner().unwrap(), b"\x0b");
+	```
+*/
+pub trait BitWrite {
+	/// Writes a single bit, writing 1 for true, 0 for false.
+	fn write_bit(&mut self, bit: bool) -> Res<()>;
+
+	/// Returns whether the most significant bit of a value is written first, i.e. whether the bits are numbered big endian.
+	fn msb_first(&self) -> bool;
+
+	/**
+		Writes the lowest `count` bits of `bits`, 8 or less. If `count` > 8, an error of kind `InvalidInput` is returned.
+
+		The default implementation writes the bits one by one with `write_bit`, in the order given by `msb_first`.
+	*/
+	fn write_bits(&mut self, bits: u8, count: u8) -> Res<()> {
+		check_bit_count(count)?;
+		for i in 0..count {
+			let mask = if self.msb_first() { 1 << (count - 1 - i) } else { 1 << i };
+			self.write_bit(bits & mask != 0)?;
+		}
+		Ok(())
+	}
+}
+
+impl<T: BitWrite + ?Sized> BitWrite for &mut T {
+	fn write_bit(&mut self, bit: bool) -> Res<()> {
+		(**self).write_bit(bit)
+	}
+
+	fn msb_first(&self) -> bool {
+		(**self).msb_first()
+	}
+
+	fn write_bits(&mut self, bits: u8, count: u8) -> Res<()> {
+		(**self).write_bits(bits, count)
+	}
+}
+
+impl<T: BitWrite + ?Sized> BitWrite for Box<T> {
+	fn write_bit(&mut self, bit: bool) -> Res<()> {
+		(**self).write_bit(bit)
+	}
+
+	fn msb_first(&self) -> bool {
+		(**self).msb_first()
+	}
+
+	fn write_bits(&mut self, bits: u8, count: u8) -> Res<()> {
+		(**self).write_bits(bits, count)
+	}
+}
+
 /**
 	Adds bit-level writing support to something implementing [`std::io::Write`].
 
 	This is accomplished through an internal buffer for storing partially read bytes. Note that this buffer is for correctness, not performance - if you want to improve performance by buffering, use [`std::io::BufWriter`] as the `BitWriter`'s write target.
 
-	When the `BitWriter` is dropped, the partially written byte will be written out. However, any errors that happen in the process of flushing the buffer when the writer is dropped will be ignored. Code that wishes to handle such errors must manually call `flush` before the writer is dropped.
+	Errors of kind `Interrupted` returned by the underlying writer are retried internally. Writing bits and bytes fails with any other error without changing the `BitWriter`, so it can be retried, e.g. after a `WouldBlock` error from a non-blocking target. Operations made up of several writes, like `align_to` and `reserve_bits`, keep the bits written before the error, as documented on each of them. Errors of bit-level operations wrap a `BitError` describing the position in the stream and the context labels at which they occurred.
+
+	When the `BitWriter` is dropped, the partially written byte will be written out. However, any errors that happen in the process of flushing the buffer when the writer is dropped will be ignored. Code that wishes to handle such errors must manually call `finish`, `into_inner` or `align` before the writer is dropped. To catch writers that are dropped with pending bits, use `set_drop_policy`.
 
 	To use this writer, you'll have to choose a bit endianness to write in. The bit endianness determines the direction in which bits in a byte will be written. Note that this is distinct from byte endianness, and e.g. a format which is little endian at the byte level is not necessarily little endian at the bit level.
 
@@ -47,6 +150,22 @@ pub struct BitWriter<E: BitEndianness, W: Write> {
 	bit_offset: u8,
 	/// Storage for remaining bits after an unaligned write operation.
 	bit_buffer: u8,
+	padding: Padding,
+	/// Boundary in bits to align to in `into_inner`, `finish` and on drop.
+	final_alignment: u32,
+	drop_policy: DropPolicy,
+	/// Number of bits written, excluding padding.
+	bit_count: u64,
+	/// Position in bits relative to the start of the stream.
+	position: u64,
+	/// Furthest position reached, for seeking relative to the end of the stream.
+	end: u64,
+	/// Value of `position` when `set_mark` was last called.
+	mark: u64,
+	/// Value of `bit_count` when padding was last written.
+	padded_at: u64,
+	/// Labels of the fields being written, for error messages.
+	context: Vec<Cow<'static, str>>,
 	buffer: Vec<u8>,
 	phantom: std::marker::PhantomData<E>,
 }
@@ -80,6 +199,15 @@ impl<E: BitEndianness, W: Write> BitWriter<E, W> {
 			inner: Some(inner),
 			bit_offset: 0,
 			bit_buffer: 0,
+			padding: Padding::Zeros,
+			final_alignment: 8,
+			drop_policy: DropPolicy::Align,
+			bit_count: 0,
+			position: 0,
+			end: 0,
+			mark: 0,
+			padded_at: 0,
+			context: vec![],
 			buffer: vec![0; capacity],
 			phantom: std::marker::PhantomData,
 		}
@@ -91,11 +219,140 @@ impl<E: BitEndianness, W: Write> BitWriter<E, W> {
 		self.bit_offset == 0
 	}
 
-	/// Aligns to byte boundary, skipping a partial byte if the `BitWriter` was not aligned.
+	/// Returns the offset of the next bit to write within the current byte, 0 if the `BitWriter` is aligned.
+	pub fn bit_offset(&self) -> u8 {
+		self.bit_offset
+	}
+
+	/**
+		Returns the position in bits relative to the start of the stream, which starts at the creation of the `BitWriter`.
+
+		Unlike the number of bits returned by `finish`, this includes padding, and bits written through the `Write` implementation are included as well. Seeking changes the position. Bytes written directly to the underlying writer are not taken into account.
+
+		# Examples
+
+		```
+		# use endio_bit::BEBitWriter;
+		# use std::io::Write;
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.write_bits(0x05, 3).unwrap();
+		writer.set_mark();
+		writer.write_all(b"\x12\x34").unwrap();
+		writer.align().unwrap();
+		assert_eq!(writer.bit_position(), 24);
+		assert_eq!(writer.bits_since_mark(), 21);
+		assert_eq!(writer.bit_offset(), 0);
+		```
+	*/
+	pub fn bit_position(&self) -> u64 {
+		self.position
+	}
+
+	/// Sets the mark to the current position, for measuring the bits written from it with `bits_since_mark`.
+	pub fn set_mark(&mut self) {
+		self.mark = self.position;
+	}
+
+	/// Returns the number of bits from the last call of `set_mark`, or from the creation of the `BitWriter` if there was none, to the current position. Returns 0 if the position is before the mark, e.g. after seeking back.
+	pub fn bits_since_mark(&self) -> u64 {
+		self.position.saturating_sub(self.mark)
+	}
+
+	/**
+		Sets the bits used to pad the stream on alignment.
+
+		By default, the stream is padded with 0-bits. The padding is also applied when the writer is aligned by `into_inner`, `finish` or on drop.
+	*/
+	pub fn set_padding(&mut self, padding: Padding) {
+		self.padding = padding;
+	}
+
+	/**
+		Sets the boundary in bits that the stream is aligned to by `into_inner`, `finish` and on drop, as with `align_to`. The default is 8, i.e. byte alignment.
+
+		If `boundary` is 0, an error of kind `InvalidInput` is returned and the boundary is left unchanged.
+	*/
+	pub fn set_final_alignment(&mut self, boundary: u32) -> Res<()> {
+		if boundary == 0 {
+			return Err(self.error(0, Error::new(ErrorKind::InvalidInput, "alignment boundary must not be 0")));
+		}
+		self.final_alignment = boundary;
+		Ok(())
+	}
+
+	/// Sets what happens to a partially written byte when the `BitWriter` is dropped.
+	pub fn set_drop_policy(&mut self, policy: DropPolicy) {
+		self.drop_policy = policy;
+	}
+
+	/// Pushes a label onto the context stack, describing the field being written in error messages.
+	pub fn push_context(&mut self, label: impl Into<Cow<'static, str>>) {
+		self.context.push(label.into());
+	}
+
+	/// Pops the innermost label from the context stack.
+	pub fn pop_context(&mut self) {
+		self.context.pop();
+	}
+
+	/// Calls `f` with `label` pushed onto the context stack, popping it afterwards even if `f` fails.
+	pub fn with_context<T>(&mut self, label: impl Into<Cow<'static, str>>, f: impl FnOnce(&mut Self) -> Res<T>) -> Res<T> {
+		self.push_context(label);
+		let res = f(self);
+		self.pop_context();
+		res
+	}
+
+	/// Wraps `error` in a `BitError` at the current position.
+	fn error(&self, count: u32, error: Error) -> Error {
+		BitError::new(self.position, count, &self.context, true, error).into()
+	}
+
+	/// Aligns to byte boundary, padding a partial byte if the `BitWriter` was not aligned. Equivalent to `align_to(8)`.
 	pub fn align(&mut self) -> Res<()> {
-		if !self.is_aligned() {
-			self.flush_buffer()?;
-			self.bit_offset = 0;
+		self.align_to(8)
+	}
+
+	/**
+		Aligns to a boundary of `boundary` bits relative to the start of the stream, padding as configured by `set_padding`.
+
+		The stream starts at the creation of the `BitWriter`. Bytes written directly to the underlying writer are not taken into account.
+
+		Padding is written byte by byte. If an error occurs, the padding written so far is kept, and calling `align_to` again completes it. If `boundary` is 0, an error of kind `InvalidInput` is returned.
+
+		# Examples
+
+		```
+		use endio_bit::{BEBitWriter, Padding};
+
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.set_padding(Padding::StopBit);
+		writer.write_bits(0x05, 3).unwrap();
+		writer.align_to(32).unwrap();
+		let vec = writer.into_inner().unwrap();
+		assert_eq!(vec, b"\xb0\0\0\0");
+		```
+	*/
+	pub fn align_to(&mut self, boundary: u32) -> Res<()> {
+		if boundary == 0 {
+			return Err(self.error(0, Error::new(ErrorKind::InvalidInput, "alignment boundary must not be 0")));
+		}
+		let boundary = boundary as u64;
+		let remaining = |writer: &Self| (boundary - writer.position % boundary) as u32;
+		if self.padding == Padding::StopBit && self.padded_at != self.bit_count {
+			self.push_bit(true).map_err(|e| self.error(remaining(self), e))?;
+			self.position += 1;
+			self.padded_at = self.bit_count;
+		}
+		while !self.position.is_multiple_of(boundary) {
+			let pattern = match self.padding {
+				Padding::Zeros | Padding::StopBit => 0x00,
+				Padding::Ones => 0xff,
+				Padding::Pattern(pattern) => pattern,
+			};
+			self.push_bit(pattern & E::shift_lsb(E::shift_msb(0xff, 7), self.bit_offset) != 0).map_err(|e| self.error(remaining(self), e))?;
+			self.position += 1;
+			self.padded_at = self.bit_count;
 		}
 		Ok(())
 	}
@@ -109,7 +366,7 @@ impl<E: BitEndianness, W: Write> BitWriter<E, W> {
 		# let inner = writer.get_ref();
 		# inner.clear();
 		```
-	**/
+	*/
 	pub fn get_ref(&self) -> &W {
 		self.inner.as_ref().unwrap()
 	}
@@ -119,7 +376,9 @@ impl<E: BitEndianness, W: Write> BitWriter<E, W> {
 
 		Mutable operations on the underlying writer will corrupt this `BitWriter` if it is not aligned, so the reference is only returned if the `BitWriter` is aligned.
 
-		Panics if the `BitWriter` is not aligned.
+		# Panics
+
+		Panics if the `BitWriter` is not aligned. Use `try_get_mut` to avoid the panic.
 	*/
 	pub fn get_mut(&mut self) -> &mut W {
 		if !self.is_aligned() {
@@ -128,10 +387,22 @@ impl<E: BitEndianness, W: Write> BitWriter<E, W> {
 		self.inner.as_mut().unwrap()
 	}
 
+	/// Gets a mutable reference to the underlying writer like `get_mut`, but returns `None` instead of panicking if the `BitWriter` is not aligned.
+	pub fn try_get_mut(&mut self) -> Option<&mut W> {
+		if !self.is_aligned() {
+			return None;
+		}
+		self.inner.as_mut()
+	}
+
 	/**
 		Gets a mutable reference to the underlying writer.
 
 		Use with care: Any writing/seeking/etc operation on the underlying writer will corrupt this `BitWriter` if it is not aligned.
+
+		# Safety
+
+		The caller must not modify the state of the underlying writer while the `BitWriter` is not aligned.
 	*/
 	pub unsafe fn get_mut_unchecked(&mut self) -> &mut W {
 		self.inner.as_mut().unwrap()
@@ -140,21 +411,50 @@ impl<E: BitEndianness, W: Write> BitWriter<E, W> {
 	/**
 		Unwraps this `BitWriter`, returning the underlying writer.
 
-		The buffer for partial writes will be flushed before returning the writer. If an error occurs during the flushing it will be returned.
+		The stream will be aligned to the final alignment boundary before returning the writer. If an error occurs during the alignment it will be returned.
 	*/
 	pub fn into_inner(mut self) -> Result<W, IntoInnerError<Self>> {
-		match self.align() {
+		match self.align_final() {
 			Ok(()) => Ok(self.inner.take().unwrap()),
 			Err(e) => Err(IntoInnerError(self, e)),
 		}
 	}
 
-	fn flush_buffer(&mut self) -> Res<()> {
-		let mut temp = [0; 1];
-		temp[0] = self.bit_buffer;
-		unsafe { self.get_mut_unchecked() }.write(&temp)?;
-		self.bit_buffer = 0;
-		Ok(())
+	/**
+		Finishes writing, returning the underlying writer and the number of bits written.
+
+		The stream is aligned to the final alignment boundary, and the underlying writer is flushed. Unlike on drop, errors are returned. The returned bit count excludes padding.
+
+		# Examples
+
+		```
+		# use endio_bit::BEBitWriter;
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.write_bits(0x1f, 5).unwrap();
+		writer.write_bits(0xff, 8).unwrap();
+		let (vec, bit_count) = writer.finish().unwrap();
+		assert_eq!(vec, b"\xff\xf8");
+		assert_eq!(bit_count, 13);
+		```
+	*/
+	pub fn finish(mut self) -> Result<(W, u64), IntoInnerError<Self>> {
+		if let Err(e) = self.align_final().and_then(|()| self.inner.as_mut().unwrap().flush()) {
+			return Err(IntoInnerError(self, e));
+		}
+		Ok((self.inner.take().unwrap(), self.bit_count))
+	}
+
+	/// Aligns to the final alignment boundary, unless the writer has seeked back from the end of the stream, where padding would overwrite the bits following the position.
+	fn align_final(&mut self) -> Res<()> {
+		if self.position < self.end {
+			return Err(self.error(0, Error::new(ErrorKind::InvalidInput, "not at the end of the stream, seek to it before finishing")));
+		}
+		self.align_to(self.final_alignment)
+	}
+
+	/// Writes a byte to the underlying writer, without modifying the state of the `BitWriter`.
+	fn write_byte(&mut self, byte: u8) -> Res<()> {
+		unsafe { self.get_mut_unchecked() }.write_all(&[byte])
 	}
 
 	/**
@@ -177,28 +477,36 @@ impl<E: BitEndianness, W: Write> BitWriter<E, W> {
 		let vec = writer.into_inner().unwrap();
 		assert_eq!(vec[0], 0x01);
 		```
-	**/
+	*/
 	pub fn write_bit(&mut self, bit: bool) -> Res<()> {
+		self.push_bit(bit).map_err(|e| self.error(1, e))?;
+		self.bit_count += 1;
+		self.position += 1;
+		Ok(())
+	}
+
+	/// Writes a single bit, without counting it.
+	fn push_bit(&mut self, bit: bool) -> Res<()> {
+		let mut bit_buffer = self.bit_buffer;
 		if bit {
-			self.bit_buffer |= E::shift_lsb(E::shift_msb(0xff, 7), self.bit_offset);
+			bit_buffer |= E::shift_lsb(E::shift_msb(0xff, 7), self.bit_offset);
 		}
-		self.bit_offset = (self.bit_offset + 1) % 8;
-		if self.is_aligned() {
-			self.flush_buffer()?;
+		let bit_offset = (self.bit_offset + 1) % 8;
+		if bit_offset == 0 {
+			self.write_byte(bit_buffer)?;
+			bit_buffer = 0;
 		}
+		self.bit_buffer = bit_buffer;
+		self.bit_offset = bit_offset;
 		Ok(())
 	}
 
 	/**
 		Writes 8 bits or less.
 
-		The lowest `count` bits will be used, others will be ignored.
-
-		Writing more than 8 bits is intentionally not supported to keep the interface simple. Writing more can be accomplished by writing bytes and then writing any leftover bits.
-
-		# Panics
+		The lowest `count` bits will be used, others will be ignored. Use `write_bits_checked` to detect values that don't fit into `count` bits.
 
-		Panics if `count` > 8.
+		Writing more than 8 bits is intentionally not supported to keep the interface simple. Writing more can be accomplished by writing bytes and then writing any leftover bits. If `count` > 8, an error of kind `InvalidInput` is returned.
 
 		# Examples
 
@@ -219,23 +527,241 @@ impl<E: BitEndianness, W: Write> BitWriter<E, W> {
 		```
 	*/
 	pub fn write_bits(&mut self, bits: u8, count: u8) -> Res<()> {
-		assert!(count <= 8);
+		check_bit_count(count).map_err(|e| self.error(count as u32, e))?;
+		if count == 0 {
+			return Ok(());
+		}
 		let start = self.bit_offset;
 		let end = start + count;
 		let bits = bits << (8 - count);
 		let bits = E::align_right(bits, count);
-		self.bit_buffer |= E::shift_lsb(bits, start);
+		let mut bit_buffer = self.bit_buffer | E::shift_lsb(bits, start);
 		if end >= 8 {
-			self.flush_buffer()?;
+			self.write_byte(bit_buffer).map_err(|e| self.error(count as u32, e))?;
+			bit_buffer = 0;
 		}
 		if end > 8 {
-			self.bit_buffer = E::shift_msb(bits, 8 - start);
+			bit_buffer = E::shift_msb(bits, 8 - start);
 		}
+		self.bit_buffer = bit_buffer;
 		self.bit_offset = end % 8;
+		self.bit_count += count as u64;
+		self.position += count as u64;
+		Ok(())
+	}
+
+	/**
+		Writes 8 bits or less, like `write_bits`, but returns an error of kind `InvalidInput` if `bits` doesn't fit into `count` bits. Nothing is written in that case, or if `count` > 8.
+
+		# Examples
+
+		```
+		# use endio_bit::BEBitWriter;
+		# use std::io::ErrorKind;
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.write_bits_checked(31, 5).unwrap();
+		assert_eq!(writer.write_bits_checked(32, 5).unwrap_err().kind(), ErrorKind::InvalidInput);
+		```
+	*/
+	pub fn write_bits_checked(&mut self, bits: u8, count: u8) -> Res<()> {
+		check_bit_count(count).map_err(|e| self.error(count as u32, e))?;
+		if count < 8 && bits >> count != 0 {
+			return Err(self.error(count as u32, Error::new(ErrorKind::InvalidInput, format!("value {} doesn't fit into {} bits", bits, count))));
+		}
+		self.write_bits(bits, count)
+	}
+
+	/**
+		Writes a signed value as a two's complement field of 8 bits or less, returning an error of kind `InvalidInput` if `value` doesn't fit into `count` bits. Nothing is written in that case, or if `count` > 8.
+
+		# Examples
+
+		```
+		# use endio_bit::BEBitWriter;
+		# use std::io::ErrorKind;
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.write_signed_bits_checked(-16, 5).unwrap();
+		assert_eq!(writer.write_signed_bits_checked(16, 5).unwrap_err().kind(), ErrorKind::InvalidInput);
+		let vec = writer.into_inner().unwrap();
+		assert_eq!(vec[0], 0x80);
+		```
+	*/
+	pub fn write_signed_bits_checked(&mut self, value: i8, count: u8) -> Res<()> {
+		check_bit_count(count).map_err(|e| self.error(count as u32, e))?;
+		let fits = match count {
+			0 => value == 0,
+			_ => (value as i16) >= -(1 << (count - 1)) && (value as i16) < 1 << (count - 1),
+		};
+		if !fits {
+			return Err(self.error(count as u32, Error::new(ErrorKind::InvalidInput, format!("value {} doesn't fit into {} signed bits", value, count))));
+		}
+		self.write_bits(value as u8, count)
+	}
+
+	/**
+		Reserves a field of `count` bits by writing 0-bits, to be filled with `fill_placeholder` once its value is known, e.g. for a length prefix.
+
+		Filling the placeholder requires the underlying writer to support reading and seeking, as e.g. `std::io::Cursor<Vec<u8>>` does. If an error occurs, the 0-bits written so far are kept.
+	*/
+	pub fn reserve_bits(&mut self, count: u64) -> Res<Placeholder> {
+		let position = self.position;
+		let mut remaining = count;
+		while remaining > 0 {
+			let chunk = std::cmp::min(remaining, 8) as u8;
+			self.write_bits(0, chunk)?;
+			remaining -= chunk as u64;
+		}
+		Ok(Placeholder { position, count })
+	}
+}
+
+impl<E: BitEndianness, W: Read + Write + Seek> BitWriter<E, W> {
+	/**
+		Seeks to a position in bits relative to the start of the stream, which starts at the creation of the `BitWriter`. `SeekFrom::End` is relative to the furthest position written so far. Returns the new position.
+
+		When seeking away from a partially written byte, it's written out, keeping the bits of the existing byte at the positions that haven't been written. When seeking into the middle of an existing byte, its bits before the new position are kept. Reading from the underlying writer is only used for this. Since padding would overwrite the bits following the position, `into_inner` and `finish` fail with `InvalidInput` while the writer is seeked back from the furthest written position, and dropping it then discards the partial byte. Seek back with `seek_bits(SeekFrom::End(0))` before finishing the writer.
+
+		Bits written after seeking are counted again in the number of bits returned by `finish`. If an error occurs, the position of the `BitWriter` is unspecified.
+
+		# Examples
+
+		```
+		use endio_bit::BEBitWriter;
+		use std::io::{Cursor, SeekFrom};
+
+		let mut writer = BEBitWriter::new(Cursor::new(vec![]));
+		writer.write_bits(0xff, 8).unwrap();
+		writer.write_bits(0xff, 4).unwrap();
+		writer.seek_bits(SeekFrom::Start(6)).unwrap();
+		writer.write_bits(0x00, 4).unwrap();
+		assert_eq!(writer.seek_bits(SeekFrom::End(0)).unwrap(), 12);
+		let vec = writer.into_inner().unwrap().into_inner();
+		assert_eq!(vec, b"\xfc\x30");
+		```
+	*/
+	pub fn seek_bits(&mut self, pos: SeekFrom) -> Res<u64> {
+		self.end = std::cmp::max(self.end, self.position);
+		let target = match pos {
+			SeekFrom::Start(offset) => Some(offset),
+			SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+			SeekFrom::End(offset) => self.end.checked_add_signed(offset),
+		};
+		let target = target.ok_or_else(|| self.error(0, Error::new(ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")))?;
+		self.seek_bits_to(target).map_err(|e| self.error(0, e))?;
+		Ok(target)
+	}
+
+	fn seek_bits_to(&mut self, target: u64) -> Res<()> {
+		let inner_pos = self.inner.as_mut().unwrap().stream_position()?;
+		let start = inner_pos.checked_mul(8).map(|pos| (pos + self.bit_offset as u64).saturating_sub(self.position));
+		let target_abs = start.and_then(|start| start.checked_add(target))
+			.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))?;
+		if !self.is_aligned() {
+			let existing = self.read_existing()?;
+			let mask = E::shift_msb(0xff, 8 - self.bit_offset);
+			self.write_byte(self.bit_buffer | existing & !mask)?;
+		}
+		let bit_offset = (target_abs % 8) as u8;
+		self.inner.as_mut().unwrap().seek(SeekFrom::Start(target_abs / 8))?;
+		self.bit_buffer = if bit_offset == 0 { 0 } else { self.read_existing()? & E::shift_msb(0xff, 8 - bit_offset) };
+		self.bit_offset = bit_offset;
+		self.position = target;
+		Ok(())
+	}
+
+	/// Reads the byte at the position of the underlying writer without advancing it, returning 0 at the end of the data.
+	fn read_existing(&mut self) -> Res<u8> {
+		let inner = self.inner.as_mut().unwrap();
+		let mut byte = [0; 1];
+		let len = loop {
+			match inner.read(&mut byte) {
+				Ok(len) => break len,
+				Err(e) if e.kind() == ErrorKind::Interrupted => {}
+				Err(e) => return Err(e),
+			}
+		};
+		if len > 0 {
+			inner.seek(SeekFrom::Current(-1))?;
+		}
+		Ok(byte[0])
+	}
+
+	/**
+		Fills a placeholder reserved with `reserve_bits`, by seeking to it, calling `f` to write exactly as many bits as were reserved, and seeking back.
+
+		The bits written by `f` aren't counted again in the number of bits returned by `finish`. If `f` doesn't write exactly the reserved number of bits, an error of kind `InvalidInput` is returned after seeking back.
+
+		# Examples
+
+		```
+		use endio_bit::BEBitWriter;
+		use std::io::Cursor;
+
+		let mut writer = BEBitWriter::new(Cursor::new(vec![]));
+		writer.write_bits(0x05, 3).unwrap();
+		let length = writer.reserve_bits(5).unwrap();
+		writer.write_bits(0xff, 8).unwrap();
+		writer.write_bit(true).unwrap();
+		writer.fill_placeholder(length, |writer| writer.write_bits(9, 5)).unwrap();
+		let (cursor, bit_count) = writer.finish().unwrap();
+		assert_eq!(cursor.into_inner(), b"\xa9\xff\x80");
+		assert_eq!(bit_count, 17);
+		```
+	*/
+	pub fn fill_placeholder(&mut self, placeholder: Placeholder, f: impl FnOnce(&mut Self) -> Res<()>) -> Res<()> {
+		let position = self.position;
+		let bit_count = self.bit_count;
+		self.seek_bits(SeekFrom::Start(placeholder.position))?;
+		let res = f(self);
+		let written = self.position.checked_sub(placeholder.position);
+		self.seek_bits(SeekFrom::Start(position))?;
+		self.bit_count = bit_count;
+		res?;
+		if written != Some(placeholder.count) {
+			let message = match written {
+				Some(written) => format!("wrote {} bits into a placeholder of {} bits", written, placeholder.count),
+				None => format!("wrote into a placeholder of {} bits, ending before its start", placeholder.count),
+			};
+			let error = Error::new(ErrorKind::InvalidInput, message);
+			return Err(BitError::new(placeholder.position, std::cmp::min(placeholder.count, u32::MAX as u64) as u32, &self.context, true, error).into());
+		}
 		Ok(())
 	}
 }
 
+/// A field reserved with `BitWriter::reserve_bits`, to be filled with `BitWriter::fill_placeholder`.
+#[derive(Debug)]
+pub struct Placeholder {
+	position: u64,
+	count: u64,
+}
+
+impl Placeholder {
+	/// Returns the position of the field in bits relative to the start of the stream.
+	pub fn position(&self) -> u64 {
+		self.position
+	}
+
+	/// Returns the size of the field in bits.
+	pub fn count(&self) -> u64 {
+		self.count
+	}
+}
+
+impl<E: BitEndianness, W: Write> BitWrite for BitWriter<E, W> {
+	fn write_bit(&mut self, bit: bool) -> Res<()> {
+		BitWriter::write_bit(self, bit)
+	}
+
+	fn msb_first(&self) -> bool {
+		E::msb_first()
+	}
+
+	fn write_bits(&mut self, bits: u8, count: u8) -> Res<()> {
+		BitWriter::write_bits(self, bits, count)
+	}
+}
+
 /**
 	Write bytes to a `BitWriter` just like to [`Write`], but with bit shifting support for unaligned writes.
 
@@ -243,41 +769,113 @@ impl<E: BitEndianness, W: Write> BitWriter<E, W> {
 
 	Directly maps to [`Write`] for aligned writes.
 
+	`flush` only flushes whole bytes: a partially written byte stays pending, so flushing doesn't change the bit position of subsequent writes. To also write out the partial byte, call `align` before `flush`, which pads it as configured by `set_padding`.
+
 	[`Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
 */
 impl<E: BitEndianness, W: Write> Write for BitWriter<E, W> {
 	fn write(&mut self, buf: &[u8]) -> Res<usize> {
 		if self.is_aligned() {
-			return unsafe { self.get_mut_unchecked() }.write(buf);
+			let count_written = loop {
+				match unsafe { self.get_mut_unchecked() }.write(buf) {
+					Ok(count_written) => break count_written,
+					Err(e) if e.kind() == ErrorKind::Interrupted => {}
+					Err(e) => return Err(e),
+				}
+			};
+			self.bit_count += count_written as u64 * 8;
+			self.position += count_written as u64 * 8;
+			return Ok(count_written);
 		}
 		let mut last_byte = E::shift_lsb(self.bit_buffer, 8 - self.bit_offset);
 		for (byte, new) in buf.iter().zip(self.buffer.iter_mut()) {
 			*new = E::shift_msb(last_byte, 8 - self.bit_offset)  | E::shift_lsb(*byte, self.bit_offset);
 			last_byte = *byte;
 		}
-		self.bit_buffer = E::shift_msb(last_byte, 8 - self.bit_offset);
 		let len = std::cmp::min(buf.len(), self.buffer.len());
-		self.inner.as_mut().unwrap().write(&self.buffer[0..len])
+		let count_written = loop {
+			match self.inner.as_mut().unwrap().write(&self.buffer[0..len]) {
+				Ok(count_written) => break count_written,
+				Err(e) if e.kind() == ErrorKind::Interrupted => {}
+				Err(e) => return Err(e),
+			}
+		};
+		// the bits carried over come from the last byte the inner writer actually accepted
+		if count_written > 0 {
+			self.bit_buffer = E::shift_msb(buf[count_written - 1], 8 - self.bit_offset);
+		}
+		self.bit_count += count_written as u64 * 8;
+		self.position += count_written as u64 * 8;
+		Ok(count_written)
 	}
 
 	fn flush(&mut self) -> Res<()> {
-		if !self.is_aligned() {
-			self.flush_buffer()?;
-		}
 		unsafe { self.get_mut_unchecked() }.flush()
 	}
 }
 
-/// Flushes the buffer for unaligned writes before the `BitWriter` is dropped.
+/**
+	Seeks in bytes, like `BitWriter::seek_bits` with positions multiplied by 8. If the `BitWriter` isn't aligned, the returned position is rounded down to the byte boundary.
+*/
+impl<E: BitEndianness, W: Read + Write + Seek> Seek for BitWriter<E, W> {
+	fn seek(&mut self, pos: SeekFrom) -> Res<u64> {
+		let pos = match pos {
+			SeekFrom::Start(offset) => offset.checked_mul(8).map(SeekFrom::Start),
+			SeekFrom::Current(offset) => offset.checked_mul(8).map(SeekFrom::Current),
+			SeekFrom::End(offset) => offset.checked_mul(8).map(SeekFrom::End),
+		};
+		let pos = pos.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "invalid seek to an overflowing position"))?;
+		self.seek_bits(pos).map(|position| position / 8)
+	}
+}
+
+/// Handles the buffer for unaligned writes before the `BitWriter` is dropped, as specified by its `DropPolicy`.
 impl<E: BitEndianness, W: Write> Drop for BitWriter<E, W> {
 	fn drop(&mut self) {
-		let _ = self.align();
+		if self.inner.is_none() {
+			return;
+		}
+		match self.drop_policy {
+			DropPolicy::Align => { let _ = self.align_final(); }
+			DropPolicy::Discard => {}
+			DropPolicy::Panic => {
+				if (self.position < self.end || !self.position.is_multiple_of(self.final_alignment as u64)) && !std::thread::panicking() {
+					panic!("BitWriter dropped at bit {}, which isn't the end of the stream aligned to {} bits", self.position, self.final_alignment);
+				}
+			}
+		}
 	}
 }
 
 #[cfg(test)]
 mod tests_common {
-	use crate::BEBitWriter;
+	use std::io::{Cursor, ErrorKind, Seek, SeekFrom, Write};
+	use crate::{BEBitWriter, BitWrite, DropPolicy, Padding};
+	use crate::test_util::{retry, ErrorWriter, ShortWriter};
+
+	#[test]
+	fn would_block() {
+		let mut writer = BEBitWriter::new(ErrorWriter::new(ErrorKind::WouldBlock));
+		retry(|| writer.write_bits(0x05, 3));
+		retry(|| writer.write_bits(0x5e, 8));
+		retry(|| writer.write_bit(false));
+		retry(|| writer.write_bits(0x06, 3));
+		retry(|| writer.write_bit(true));
+		retry(|| writer.write_bits(0x03, 3));
+		writer.set_padding(Padding::Ones);
+		retry(|| writer.align());
+		assert_eq!(writer.into_inner().unwrap().data, b"\xab\xcd\x7f");
+	}
+
+	#[test]
+	fn interrupted() {
+		let mut writer = BEBitWriter::new(ErrorWriter::new(ErrorKind::Interrupted));
+		writer.write_bits(0x0a, 4).unwrap();
+		assert_eq!(writer.write(b"\xbc\xde").unwrap(), 1);
+		assert_eq!(writer.write(b"\xde").unwrap(), 1);
+		writer.write_bits(0x0f, 4).unwrap();
+		assert_eq!(writer.into_inner().unwrap().data, b"\xab\xcd\xef");
+	}
 
 	#[test]
 	fn get_ref() {
@@ -301,6 +899,18 @@ mod tests_common {
 		writer.get_mut();
 	}
 
+	#[test]
+	fn try_get_mut() {
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.write_bits(0x0a, 4).unwrap();
+		assert!(writer.try_get_mut().is_none());
+		assert_eq!(writer.align_to(0).unwrap_err().kind(), ErrorKind::InvalidInput);
+		assert_eq!(writer.set_final_alignment(0).unwrap_err().kind(), ErrorKind::InvalidInput);
+		writer.align().unwrap();
+		writer.try_get_mut().unwrap().push(0x12);
+		assert_eq!(writer.into_inner().unwrap(), b"\xa0\x12");
+	}
+
 	#[test]
 	fn into_inner() {
 		let writer = BEBitWriter::new(vec![]);
@@ -308,6 +918,185 @@ mod tests_common {
 		inner.into_boxed_slice();
 	}
 
+	#[test]
+	fn finish() {
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.write_bit(true).unwrap();
+		writer.write_all(b"\x12\x34").unwrap();
+		writer.write_bits(0x05, 3).unwrap();
+		let (vec, bit_count) = writer.finish().unwrap();
+		assert_eq!(vec, b"\x89\x1a\x50");
+		assert_eq!(bit_count, 20);
+	}
+
+	#[test]
+	fn finish_error() {
+		let mut writer = BEBitWriter::new(&mut [][..]);
+		writer.write_bit(true).unwrap();
+		let err = writer.finish().unwrap_err();
+		assert_eq!(err.error().kind(), ErrorKind::WriteZero);
+		let mut writer = err.into_inner();
+		writer.set_drop_policy(DropPolicy::Discard);
+	}
+
+	#[test]
+	fn drop_discard() {
+		let mut vec = vec![];{
+		let mut writer = BEBitWriter::new(&mut vec);
+		writer.set_drop_policy(DropPolicy::Discard);
+		writer.write_bits(0xab, 8).unwrap();
+		writer.write_bit(true).unwrap();}
+		assert_eq!(vec, b"\xab");
+	}
+
+	#[test]
+	#[should_panic]
+	fn drop_panic() {
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.set_drop_policy(DropPolicy::Panic);
+		writer.write_bit(true).unwrap();
+	}
+
+	#[test]
+	#[should_panic]
+	fn drop_panic_final_alignment() {
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.set_drop_policy(DropPolicy::Panic);
+		writer.set_final_alignment(16).unwrap();
+		writer.write_bits(0xab, 8).unwrap();
+	}
+
+	#[test]
+	fn bit_position() {
+		let mut writer = BEBitWriter::new(ShortWriter(vec![]));
+		writer.write_bit(true).unwrap();
+		writer.set_mark();
+		assert_eq!(writer.write(b"\x12\x34").unwrap(), 1);
+		assert_eq!((writer.bit_position(), writer.bits_since_mark(), writer.bit_offset()), (9, 8, 1));
+		writer.set_padding(Padding::StopBit);
+		writer.align_to(16).unwrap();
+		assert_eq!((writer.bit_position(), writer.bits_since_mark(), writer.bit_offset()), (16, 15, 0));
+		let (_, bit_count) = writer.finish().unwrap();
+		assert_eq!(bit_count, 9);
+		let mut writer = BEBitWriter::new(Cursor::new(vec![]));
+		writer.write_bits(0xff, 6).unwrap();
+		writer.set_mark();
+		writer.seek_bits(SeekFrom::Start(2)).unwrap();
+		assert_eq!((writer.bit_position(), writer.bits_since_mark(), writer.bit_offset()), (2, 0, 2));
+	}
+
+	#[test]
+	fn bit_write() {
+		fn write_nibbles(writer: &mut dyn BitWrite, high: u8, low: u8) -> std::io::Result<()> {
+			writer.write_bits(high, 4)?;
+			writer.write_bits(low, 4)
+		}
+		let mut writer = BEBitWriter::new(vec![]);
+		write_nibbles(&mut writer, 0x01, 0x02).unwrap();
+		let mut boxed: Box<dyn BitWrite> = Box::new(&mut writer);
+		write_nibbles(&mut boxed, 0x03, 0x04).unwrap();
+		assert_eq!(boxed.write_bits(0, 9).unwrap_err().kind(), ErrorKind::InvalidInput);
+		drop(boxed);
+		assert_eq!(writer.into_inner().unwrap(), b"\x12\x34");
+	}
+
+	#[test]
+	fn seek_bits() {
+		let mut writer = BEBitWriter::new(Cursor::new(b"\xff\xff\xff".to_vec()));
+		assert_eq!(writer.seek_bits(SeekFrom::Start(5)).unwrap(), 5);
+		writer.write_bits(0x00, 7).unwrap();
+		assert_eq!(writer.seek_bits(SeekFrom::Current(-2)).unwrap(), 10);
+		writer.write_bits(0x03, 2).unwrap();
+		assert_eq!(writer.seek_bits(SeekFrom::End(-1)).unwrap(), 11);
+		let err = writer.seek_bits(SeekFrom::Current(-12)).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidInput);
+		writer.seek_bits(SeekFrom::Start(24)).unwrap();
+		assert_eq!(writer.get_ref().get_ref(), b"\xf8\x3f\xff");
+	}
+
+	#[test]
+	fn seek() {
+		let mut writer = BEBitWriter::new(Cursor::new(vec![]));
+		writer.write_all(b"\x12\x34\x56").unwrap();
+		writer.write_bits(0x0a, 4).unwrap();
+		assert_eq!(writer.seek(SeekFrom::Start(1)).unwrap(), 1);
+		writer.write_bits(0xff, 8).unwrap();
+		assert_eq!(writer.stream_position().unwrap(), 2);
+		assert_eq!(writer.seek(SeekFrom::End(0)).unwrap(), 3);
+		writer.write_bits(0x0b, 4).unwrap();
+		assert_eq!(writer.into_inner().unwrap().into_inner(), b"\x12\xff\x56\xab");
+	}
+
+	#[test]
+	fn seek_bits_overflow() {
+		let mut cursor = Cursor::new(vec![0]);
+		cursor.seek(SeekFrom::End(0)).unwrap();
+		let mut writer = BEBitWriter::new(cursor);
+		let err = writer.seek_bits(SeekFrom::Start(u64::MAX)).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidInput);
+		assert_eq!(writer.seek_bits(SeekFrom::Current(0)).unwrap(), 0);
+		let mut cursor = Cursor::new(vec![]);
+		cursor.set_position(u64::MAX / 8 + 1);
+		let mut writer = BEBitWriter::new(cursor);
+		let err = writer.seek_bits(SeekFrom::Start(1)).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidInput);
+	}
+
+	#[test]
+	fn seek_bits_finish() {
+		let mut writer = BEBitWriter::new(Cursor::new(vec![]));
+		writer.write_all(b"\xff\xff").unwrap();
+		writer.seek_bits(SeekFrom::Start(3)).unwrap();
+		let err = writer.into_inner().unwrap_err();
+		assert_eq!(err.error().kind(), ErrorKind::InvalidInput);
+		let mut writer = err.into_inner();
+		writer.seek_bits(SeekFrom::End(0)).unwrap();
+		assert_eq!(writer.into_inner().unwrap().into_inner(), b"\xff\xff");
+	}
+
+	#[test]
+	fn placeholder() {
+		let mut writer = BEBitWriter::new(Cursor::new(vec![]));
+		writer.write_bit(true).unwrap();
+		let first = writer.reserve_bits(12).unwrap();
+		assert_eq!((first.position(), first.count()), (1, 12));
+		let second = writer.reserve_bits(2).unwrap();
+		writer.write_bit(true).unwrap();
+		let err = writer.fill_placeholder(second, |writer| writer.write_bits(0x03, 3)).unwrap_err();
+		assert_eq!(err.to_string(), "wrote 3 bits into a placeholder of 2 bits at bit 13 while writing 2 bits");
+		writer.fill_placeholder(first, |writer| {
+			writer.write_bits(0xab, 8)?;
+			writer.write_bits(0x0c, 4)
+		}).unwrap();
+		let (cursor, bit_count) = writer.finish().unwrap();
+		assert_eq!(cursor.into_inner(), b"\xd5\xe3");
+		assert_eq!(bit_count, 16);
+	}
+
+	#[test]
+	fn placeholder_seek_before() {
+		let mut writer = BEBitWriter::new(Cursor::new(vec![]));
+		writer.write_bits(0x0f, 4).unwrap();
+		let placeholder = writer.reserve_bits(4).unwrap();
+		writer.write_bits(0xff, 8).unwrap();
+		let err = writer.fill_placeholder(placeholder, |writer| writer.seek_bits(SeekFrom::Start(0)).map(|_| ())).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidInput);
+		assert_eq!(writer.seek_bits(SeekFrom::Current(0)).unwrap(), 16);
+		writer.write_bit(true).unwrap();
+		assert_eq!(writer.into_inner().unwrap().into_inner(), b"\xf0\xff\x80");
+	}
+
+	#[test]
+	fn drop_panic_aligned() {
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.set_drop_policy(DropPolicy::Panic);
+		writer.write_bits(0xab, 8).unwrap();
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.set_drop_policy(DropPolicy::Panic);
+		writer.write_bit(true).unwrap();
+		writer.finish().unwrap();
+	}
+
 	#[test]
 	fn align() {
 		let mut vec = vec![];{
@@ -319,12 +1108,71 @@ mod tests_common {
 		writer.write_bit(true).unwrap();}
 		assert_eq!(vec, b"\xf8\x80");
 	}
+
+	#[test]
+	fn align_ones() {
+		let mut vec = vec![];{
+		let mut writer = BEBitWriter::new(&mut vec);
+		writer.set_padding(Padding::Ones);
+		writer.write_bits(0x10, 5).unwrap();
+		writer.align().unwrap();
+		writer.write_bit(false).unwrap();}
+		assert_eq!(vec, b"\x87\x7f");
+	}
+
+	#[test]
+	fn align_stop_bit() {
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.set_padding(Padding::StopBit);
+		writer.align().unwrap();
+		writer.write_bits(0x05, 3).unwrap();
+		writer.align().unwrap();
+		writer.align().unwrap();
+		writer.write_bits(0xab, 8).unwrap();
+		writer.align().unwrap();
+		assert_eq!(writer.into_inner().unwrap(), b"\xb0\xab\x80");
+	}
+
+	#[test]
+	fn align_pattern() {
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.set_padding(Padding::Pattern(0x55));
+		writer.write_bits(0x07, 3).unwrap();
+		writer.align_to(32).unwrap();
+		assert_eq!(writer.get_ref(), b"\xf5\x55\x55\x55");
+	}
+
+	#[test]
+	fn align_to_bits() {
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.write_bit(true).unwrap();
+		writer.align_to(3).unwrap();
+		assert_eq!(writer.is_aligned(), false);
+		writer.write_bits(0x1f, 5).unwrap();
+		assert_eq!(writer.into_inner().unwrap(), b"\x9f");
+	}
+
+	#[test]
+	fn final_alignment() {
+		let mut vec = vec![];{
+		let mut writer = BEBitWriter::new(&mut vec);
+		writer.set_final_alignment(16).unwrap();
+		writer.write_bit(true).unwrap();}
+		assert_eq!(vec, b"\x80\0");
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.set_final_alignment(32).unwrap();
+		writer.write_all(b"\x12\x34\x56\x78\x9a").unwrap();
+		let (vec, bit_count) = writer.finish().unwrap();
+		assert_eq!(vec, b"\x12\x34\x56\x78\x9a\0\0\0");
+		assert_eq!(bit_count, 40);
+	}
 }
 
 #[cfg(test)]
 mod tests_be {
-	use std::io::Write;
+	use std::io::{ErrorKind, Write};
 	use crate::BEBitWriter;
+	use crate::test_util::ShortWriter;
 
 	#[test]
 	fn write_aligned() {
@@ -345,15 +1193,45 @@ mod tests_be {
 		assert_eq!(vec, b"\xaa\x8c\xae\x6e\x80");
 	}
 
+	#[test]
+	fn write_shifted_short() {
+		let mut writer = BEBitWriter::with_capacity(8, ShortWriter(vec![]));
+		writer.write_bit(true).unwrap();
+		writer.write_bit(false).unwrap();
+		writer.write_bit(true).unwrap();
+		assert_eq!(writer.write(b"Test").unwrap(), 1);
+		writer.write_all(b"est").unwrap();
+		let inner = writer.into_inner().unwrap();
+		assert_eq!(inner.0, b"\xaa\x8c\xae\x6e\x80");
+	}
+
 	#[test]
 	fn flush() {
 		let mut writer = BEBitWriter::new(vec![]);
 		writer.write_bit(true).unwrap();
 		assert_eq!(writer.get_ref(), b"");
 		writer.flush().unwrap();
+		assert_eq!(writer.get_ref(), b"");
+		writer.align().unwrap();
+		writer.flush().unwrap();
 		assert_eq!(writer.get_ref(), b"\x80");
 	}
 
+	#[test]
+	fn flush_interleaved() {
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.write_bits(0x05, 3).unwrap();
+		writer.flush().unwrap();
+		writer.write_bits(0x1f, 6).unwrap();
+		writer.flush().unwrap();
+		assert_eq!(writer.get_ref(), b"\xaf");
+		assert_eq!(writer.write(b"\x81").unwrap(), 1);
+		writer.flush().unwrap();
+		assert_eq!(writer.get_ref(), b"\xaf\xc0");
+		writer.write_bits(0x7f, 7).unwrap();
+		assert_eq!(writer.into_inner().unwrap(), b"\xaf\xc0\xff");
+	}
+
 	#[test]
 	fn write_bit() {
 		let mut vec = vec![];{
@@ -379,18 +1257,50 @@ mod tests_be {
 	}
 
 	#[test]
-	#[should_panic]
+	fn write_bits_checked() {
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.write_bits_checked(0x0a, 4).unwrap();
+		let err = writer.write_bits_checked(0x10, 4).unwrap_err();
+		assert_eq!(err.kind(), ErrorKind::InvalidInput);
+		assert_eq!(err.to_string(), "value 16 doesn't fit into 4 bits at bit 4 while writing 4 bits");
+		writer.write_bits_checked(0xff, 8).unwrap();
+		assert_eq!(writer.write_bits_checked(1, 0).unwrap_err().kind(), ErrorKind::InvalidInput);
+		writer.write_bits_checked(0x0b, 4).unwrap();
+		assert_eq!(writer.into_inner().unwrap(), b"\xaf\xfb");
+	}
+
+	#[test]
+	fn write_signed_bits_checked() {
+		let mut writer = BEBitWriter::new(vec![]);
+		writer.write_signed_bits_checked(-8, 4).unwrap();
+		writer.write_signed_bits_checked(7, 4).unwrap();
+		writer.write_signed_bits_checked(-1, 1).unwrap();
+		writer.write_signed_bits_checked(-128, 8).unwrap();
+		writer.write_signed_bits_checked(0, 0).unwrap();
+		for &(value, count) in &[(-9, 4), (8, 4), (1, 1), (-1, 0)] {
+			let err = writer.write_signed_bits_checked(value, count).unwrap_err();
+			assert_eq!(err.kind(), ErrorKind::InvalidInput);
+		}
+		writer.write_bits(0x7f, 7).unwrap();
+		assert_eq!(writer.into_inner().unwrap(), b"\x87\xc0\x7f");
+	}
+
+	#[test]
 	fn write_too_many_bits() {
-		let mut vec = vec![];
-		let mut writer = BEBitWriter::new(&mut vec);
-		writer.write_bits(0xff, 9).unwrap();
+		let mut writer = BEBitWriter::new(vec![]);
+		assert_eq!(writer.write_bits(0xff, 9).unwrap_err().kind(), ErrorKind::InvalidInput);
+		assert_eq!(writer.write_bits_checked(0xff, 9).unwrap_err().kind(), ErrorKind::InvalidInput);
+		writer.write_bits(0xff, 0).unwrap();
+		writer.write_bits(0xa5, 8).unwrap();
+		assert_eq!(writer.into_inner().unwrap(), b"\xa5");
 	}
 }
 
 #[cfg(test)]
 mod tests_le {
-	use std::io::Write;
-	use crate::LEBitWriter;
+	use std::io::{Cursor, ErrorKind, SeekFrom, Write};
+	use crate::{LEBitWriter, Padding};
+	use crate::test_util::ShortWriter;
 
 	#[test]
 	fn write_aligned() {
@@ -411,15 +1321,45 @@ mod tests_le {
 		assert_eq!(vec, b"\xa5\x2a\x9b\xa3\x03");
 	}
 
+	#[test]
+	fn write_shifted_short() {
+		let mut writer = LEBitWriter::with_capacity(8, ShortWriter(vec![]));
+		writer.write_bit(true).unwrap();
+		writer.write_bit(false).unwrap();
+		writer.write_bit(true).unwrap();
+		assert_eq!(writer.write(b"Test").unwrap(), 1);
+		writer.write_all(b"est").unwrap();
+		let inner = writer.into_inner().unwrap();
+		assert_eq!(inner.0, b"\xa5\x2a\x9b\xa3\x03");
+	}
+
 	#[test]
 	fn flush() {
 		let mut writer = LEBitWriter::new(vec![]);
 		writer.write_bit(true).unwrap();
 		assert_eq!(writer.get_ref(), b"");
 		writer.flush().unwrap();
+		assert_eq!(writer.get_ref(), b"");
+		writer.align().unwrap();
+		writer.flush().unwrap();
 		assert_eq!(writer.get_ref(), b"\x01");
 	}
 
+	#[test]
+	fn flush_interleaved() {
+		let mut writer = LEBitWriter::new(vec![]);
+		writer.write_bits(0x05, 3).unwrap();
+		writer.flush().unwrap();
+		writer.write_bits(0x1f, 6).unwrap();
+		writer.flush().unwrap();
+		assert_eq!(writer.get_ref(), b"\xfd");
+		assert_eq!(writer.write(b"\x81").unwrap(), 1);
+		writer.flush().unwrap();
+		assert_eq!(writer.get_ref(), b"\xfd\x02");
+		writer.write_bits(0x7f, 7).unwrap();
+		assert_eq!(writer.into_inner().unwrap(), b"\xfd\x02\xff");
+	}
+
 	#[test]
 	fn write_bit() {
 		let mut vec = vec![];{
@@ -445,10 +1385,31 @@ mod tests_le {
 	}
 
 	#[test]
-	#[should_panic]
+	fn align_pattern() {
+		let mut writer = LEBitWriter::new(vec![]);
+		writer.set_padding(Padding::Pattern(0x55));
+		writer.write_bits(0x07, 3).unwrap();
+		writer.align_to(16).unwrap();
+		assert_eq!(writer.into_inner().unwrap(), b"\x57\x55");
+	}
+
+	#[test]
 	fn write_too_many_bits() {
-		let mut vec = vec![];
-		let mut writer = LEBitWriter::new(&mut vec);
-		writer.write_bits(0xff, 9).unwrap();
+		let mut writer = LEBitWriter::new(vec![]);
+		assert_eq!(writer.write_bits(0xff, 9).unwrap_err().kind(), ErrorKind::InvalidInput);
+		assert_eq!(writer.write_bits_checked(0xff, 9).unwrap_err().kind(), ErrorKind::InvalidInput);
+		writer.write_bits(0xff, 0).unwrap();
+		writer.write_bits(0xa5, 8).unwrap();
+		assert_eq!(writer.into_inner().unwrap(), b"\xa5");
+	}
+
+	#[test]
+	fn seek_bits() {
+		let mut writer = LEBitWriter::new(Cursor::new(b"\xff\xff".to_vec()));
+		writer.seek_bits(SeekFrom::Start(3)).unwrap();
+		writer.write_bits(0x00, 3).unwrap();
+		writer.seek_bits(SeekFrom::Start(12)).unwrap();
+		writer.write_bits(0x05, 4).unwrap();
+		assert_eq!(writer.get_ref().get_ref(), b"\xc7\x5f");
 	}
 }